@@ -0,0 +1,86 @@
+// the cost of each `Interpolation` mode per oscillator sample, and the THD+N it leaves on
+// a sine with 8 samples per period, short enough for the modes to differ
+// `cargo bench -- sinc` only runs the modes whose name contains "sinc"
+use std::hint::black_box;
+use std::time::Instant;
+
+use modelp::math;
+use modelp::synth::{Interpolation, Wavetable, WavetableBank, WavetableKind};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const SAMPLES: usize = 1 << 20;
+const RUNS: usize = 5;
+// a length and cycle counts for the THD+N measurement, about 1 and 10 kHz
+const THD_LENGTH: usize = 1 << 14;
+const THD_CYCLES: [usize; 2] = [341, 3413];
+// periods of the sine per table cycle, 2048 / 256 = 8 samples each
+const THD_HARMONIC: usize = 256;
+
+// the fastest of a few runs, in nanoseconds per sample
+fn time_per_sample(wavetable: &Wavetable, interpolation: Interpolation) -> f64 {
+    // a sweep, so that every mip level and crossfade is read
+    let frequencies: Vec<f32> = (0..SAMPLES)
+        .map(|i| 20.0 * 1000.0_f32.powf(i as f32 / SAMPLES as f32))
+        .collect();
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let mut phase: f32 = 0.0;
+            let mut sum: f32 = 0.0;
+            for frequency in &frequencies {
+                sum += wavetable.at(
+                    phase,
+                    0.3,
+                    *frequency,
+                    SAMPLE_RATE,
+                    black_box(interpolation),
+                );
+                phase = (phase + std::f32::consts::TAU * frequency / SAMPLE_RATE)
+                    .rem_euclid(std::f32::consts::TAU);
+            }
+            black_box(sum);
+            start.elapsed().as_nanos() as f64 / SAMPLES as f64
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn thd_n_db(sine: &Wavetable, cycles: usize, interpolation: Interpolation) -> f32 {
+    // the table holds `THD_HARMONIC` periods, so it is read that much slower
+    let period = THD_HARMONIC * THD_LENGTH;
+    let frequency = cycles as f32 * SAMPLE_RATE / period as f32;
+    let signal: Vec<f32> = (0..THD_LENGTH)
+        .map(|i| {
+            let phase = std::f64::consts::TAU * ((cycles * i) % period) as f64 / period as f64;
+            sine.at(phase as f32, 0.0, frequency, SAMPLE_RATE, interpolation)
+        })
+        .collect();
+
+    math::thd_n_db(&signal, cycles)
+}
+
+fn main() {
+    // cargo passes `--bench` along
+    let filter = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or_default()
+        .to_lowercase();
+    let saw = WavetableBank::new().get(WavetableKind::Saw);
+    let sine = Wavetable::harmonic_sine(THD_HARMONIC);
+
+    for interpolation in Interpolation::ALL {
+        let name = interpolation.to_string();
+        if !name.to_lowercase().contains(&filter) {
+            continue;
+        }
+        let ns = time_per_sample(&saw, interpolation);
+        let thd_n: Vec<String> = THD_CYCLES
+            .iter()
+            .map(|cycles| format!("{:.1} dB", thd_n_db(&sine, *cycles, interpolation)))
+            .collect();
+        println!(
+            "{name:<14} {ns:>6.1} ns/sample   THD+N {}",
+            thd_n.join(" / ")
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::midi_file::{self, MidiFileError};
+use crate::midi_input::{MidiInput, MidiInputError};
+use crate::params::ParamState;
+use crate::patch::{Patch, PatchError};
+use crate::script::{self, ScriptError};
+use crate::synth::{
+    output_devices, OfflineSynth, Synth, SynthError, Wavetable, WavetableError, WavetableKind,
+    WAVETABLE_DIR_ENV,
+};
+
+pub const USAGE: &str = "\
+usage: ModelP [COMMAND]
+
+without a command, opens the synth window
+
+commands:
+  play [--patch FILE] [--midi PORT] [--device NAME]
+      plays from a MIDI input without the window until interrupted, the first input
+      and the default audio output unless named; the MIDI mappings of the config apply
+  render INPUT --output FILE.wav [--patch FILE] [--sample-rate HZ] [--tail SECONDS]
+      renders a MIDI file (.mid) or an event script to a 32-bit float WAV file
+  list-devices
+      lists the audio outputs and the MIDI inputs
+  info
+      shows the version, the default devices and where the config is
+  help
+      shows this
+
+an event script has one event per line after its time in seconds:
+  0.0  on C4 [velocity]
+  1.5  off C4
+  1.5  set position 0.5
+parameters go by the names the config uses for MIDI mappings";
+
+const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+// rendered after the last event, for the release and the effect tails
+const DEFAULT_TAIL_S: f64 = 2.0;
+// rendered at most between two events
+const BLOCK_FRAMES: usize = 512;
+
+pub enum Command {
+    Gui,
+    Play {
+        patch: Option<PathBuf>,
+        midi_port: Option<String>,
+        device: Option<String>,
+    },
+    Render {
+        input: PathBuf,
+        output: PathBuf,
+        patch: Option<PathBuf>,
+        sample_rate: u32,
+        tail_s: f64,
+    },
+    ListDevices,
+    Info,
+    Help,
+}
+
+impl Command {
+    // the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut args = args.into_iter();
+        let Some(command) = args.next() else {
+            return Ok(Command::Gui);
+        };
+
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        while let Some(arg) = args.next() {
+            if let Some(option) = arg.strip_prefix("--") {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::Usage(format!("--{option} needs a value")))?;
+                options.push((option.to_string(), value));
+            } else {
+                positional.push(arg);
+            }
+        }
+        let mut option = |name: &str| {
+            options
+                .iter()
+                .position(|(option, _)| option == name)
+                .map(|index| options.remove(index).1)
+        };
+
+        let parsed = match command.as_str() {
+            "play" => Command::Play {
+                patch: option("patch").map(PathBuf::from),
+                midi_port: option("midi"),
+                device: option("device"),
+            },
+            "render" => {
+                if positional.len() != 1 {
+                    return Err(CliError::Usage("render takes one input file".to_string()));
+                }
+                let sample_rate = match option("sample-rate") {
+                    Some(rate) => rate
+                        .parse()
+                        .ok()
+                        .filter(|rate| (8_000..=384_000).contains(rate))
+                        .ok_or_else(|| CliError::Usage(format!("bad sample rate {rate:?}")))?,
+                    None => DEFAULT_SAMPLE_RATE,
+                };
+                let tail_s = match option("tail") {
+                    Some(tail) => tail
+                        .parse()
+                        .ok()
+                        .filter(|tail: &f64| tail.is_finite() && *tail >= 0.0)
+                        .ok_or_else(|| CliError::Usage(format!("bad tail {tail:?}")))?,
+                    None => DEFAULT_TAIL_S,
+                };
+                Command::Render {
+                    input: PathBuf::from(positional.remove(0)),
+                    output: option("output")
+                        .map(PathBuf::from)
+                        .ok_or_else(|| CliError::Usage("render needs --output".to_string()))?,
+                    patch: option("patch").map(PathBuf::from),
+                    sample_rate,
+                    tail_s,
+                }
+            }
+            "list-devices" => Command::ListDevices,
+            "info" => Command::Info,
+            "help" | "--help" | "-h" => Command::Help,
+            _ => return Err(CliError::Usage(format!("unknown command {command:?}"))),
+        };
+
+        if let Some(arg) = positional.first() {
+            return Err(CliError::Usage(format!("unexpected argument {arg:?}")));
+        }
+        if let Some((option, _)) = options.first() {
+            return Err(CliError::Usage(format!("unknown option --{option}")));
+        }
+        Ok(parsed)
+    }
+}
+
+// everything but `Command::Gui`, which is the app's
+pub fn run(command: Command) -> Result<(), CliError> {
+    match command {
+        Command::Gui => unreachable!("the window is opened by main"),
+        Command::Play {
+            patch,
+            midi_port,
+            device,
+        } => play(load_patch(patch.as_deref())?, midi_port, device),
+        Command::Render {
+            input,
+            output,
+            patch,
+            sample_rate,
+            tail_s,
+        } => render(
+            &input,
+            &output,
+            load_patch(patch.as_deref())?,
+            sample_rate,
+            tail_s,
+        ),
+        Command::ListDevices => {
+            list_devices();
+            Ok(())
+        }
+        Command::Info => {
+            info();
+            Ok(())
+        }
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+    }
+}
+
+fn load_patch(path: Option<&Path>) -> Result<Patch, CliError> {
+    match path {
+        Some(path) => Patch::from_disk(path).map_err(CliError::Patch),
+        None => Ok(Patch::default()),
+    }
+}
+
+// a user wavetable is imported again from the file the patch names
+fn user_wavetable(patch: &Patch) -> Result<Option<Wavetable>, CliError> {
+    match (patch.wavetable, &patch.wavetable_file) {
+        (WavetableKind::User(_), Some(path)) => Wavetable::from_disk(path)
+            .map(Some)
+            .map_err(CliError::Wavetable),
+        (WavetableKind::User(_), None) => Err(CliError::UnknownWavetable(patch.wavetable)),
+        _ => Ok(None),
+    }
+}
+
+fn play(
+    mut patch: Patch,
+    midi_port: Option<String>,
+    device: Option<String>,
+) -> Result<(), CliError> {
+    let port = match midi_port {
+        Some(port) => port,
+        None => MidiInput::ports()
+            .into_iter()
+            .next()
+            .ok_or(CliError::NoMidiInput)?,
+    };
+    let mappings = Config::load()
+        .map(|config| config.midi_mappings)
+        .unwrap_or_else(|err| {
+            eprintln!("failed to load the config ({err}), playing without MIDI mappings");
+            Vec::new()
+        });
+    let wavetable = user_wavetable(&patch)?;
+
+    let mut synth = Synth::with_output_device(device.as_deref()).map_err(CliError::Synth)?;
+    if let Some(wavetable) = wavetable {
+        patch.wavetable = synth.add_wavetable(wavetable);
+    }
+    for event in patch.events() {
+        synth.send_event(event);
+    }
+
+    let events = synth.event_sender();
+    let mut midi_input = MidiInput::new(Arc::new(Mutex::new(patch.retuning.clone())));
+    let params = Mutex::new(ParamState::new(patch));
+    midi_input
+        .connect(&port, synth.event_sender(), move |cc| {
+            let mut params = params.lock().unwrap();
+            for mapping in mappings.iter().filter(|m| m.controller == cc.controller) {
+                let value = mapping.param.range().value(mapping.position(cc.value));
+                if let Some(event) = params.set(mapping.param, value) {
+                    let _ = events.send(event);
+                }
+            }
+        })
+        .map_err(CliError::MidiInput)?;
+
+    eprintln!(
+        "playing from {port} at {} Hz, ctrl+c to stop",
+        synth.sample_rate()
+    );
+    loop {
+        std::thread::park();
+    }
+}
+
+fn render(
+    input: &Path,
+    output: &Path,
+    mut patch: Patch,
+    sample_rate: u32,
+    tail_s: f64,
+) -> Result<(), CliError> {
+    let is_midi = input.extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("mid") || extension.eq_ignore_ascii_case("midi")
+    });
+    let events = if is_midi {
+        midi_file::read(input).map_err(CliError::MidiFile)?
+    } else {
+        script::read(input, &mut ParamState::new(patch.clone())).map_err(CliError::Script)?
+    };
+
+    let wavetable = user_wavetable(&patch)?;
+
+    let mut synth = OfflineSynth::new(sample_rate as f32);
+    if let Some(wavetable) = wavetable {
+        patch.wavetable = synth.add_wavetable(wavetable);
+    }
+    for event in patch.events() {
+        synth.send_event(event);
+    }
+
+    let frame_of = |time: f64| (time * sample_rate as f64).round() as usize;
+    let end = events.last().map_or(0.0, |(time, _)| *time) + tail_s;
+    let total_frames = frame_of(end);
+    let spec = hound::WavSpec {
+        channels: OfflineSynth::CHANNELS as u16,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(output, spec).map_err(CliError::Wav)?;
+
+    // the blocks end where the next event is, so that every event lands on its own frame
+    let mut buffer = vec![0.0; BLOCK_FRAMES * OfflineSynth::CHANNELS];
+    let mut events = events.into_iter().peekable();
+    let mut frame = 0;
+    while frame < total_frames {
+        while let Some((_, event)) = events.next_if(|(time, _)| frame_of(*time) <= frame) {
+            synth.send_event(event);
+        }
+        let next = events
+            .peek()
+            .map_or(total_frames, |(time, _)| frame_of(*time))
+            .min(total_frames);
+        let frames = (next - frame).min(BLOCK_FRAMES);
+        let block = &mut buffer[..frames * OfflineSynth::CHANNELS];
+        synth.render(block);
+        for sample in block.iter() {
+            writer.write_sample(*sample).map_err(CliError::Wav)?;
+        }
+        frame += frames;
+    }
+    writer.finalize().map_err(CliError::Wav)?;
+
+    eprintln!(
+        "rendered {:.1} s to {}",
+        total_frames as f64 / sample_rate as f64,
+        output.display()
+    );
+    Ok(())
+}
+
+fn list_devices() {
+    println!("audio outputs:");
+    for (name, is_default) in output_devices() {
+        let default = if is_default { " (default)" } else { "" };
+        println!("  {name}{default}");
+    }
+    println!("MIDI inputs:");
+    for port in MidiInput::ports() {
+        println!("  {port}");
+    }
+}
+
+fn info() {
+    println!("ModelP {}", env!("CARGO_PKG_VERSION"));
+    let default_output = output_devices()
+        .into_iter()
+        .find(|(_, is_default)| *is_default)
+        .map_or("none".to_string(), |(name, _)| name);
+    println!("default audio output: {default_output}");
+    let midi_inputs = MidiInput::ports();
+    println!(
+        "MIDI input: {}",
+        midi_inputs.first().map_or("none", |port| port.as_str())
+    );
+    println!("config: {}", Config::path().display());
+    match Config::load() {
+        Ok(config) => println!("MIDI mappings: {}", config.midi_mappings.len()),
+        Err(err) => println!("MIDI mappings: {err}"),
+    }
+    match std::env::var_os(WAVETABLE_DIR_ENV) {
+        Some(dir) => println!("wavetables: {}", Path::new(&dir).display()),
+        None => println!("wavetables: embedded"),
+    }
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Gui(eframe::Error),
+    Patch(PatchError),
+    Script(ScriptError),
+    MidiFile(MidiFileError),
+    MidiInput(MidiInputError),
+    NoMidiInput,
+    Synth(SynthError),
+    Wavetable(WavetableError),
+    UnknownWavetable(WavetableKind),
+    Wav(hound::Error),
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}, see `ModelP help`", message),
+            CliError::Gui(err) => write!(f, "{}", err),
+            CliError::Patch(err) => write!(f, "{}", err),
+            CliError::Script(err) => write!(f, "{}", err),
+            CliError::MidiFile(err) => write!(f, "{}", err),
+            CliError::MidiInput(err) => write!(f, "{}", err),
+            CliError::NoMidiInput => write!(f, "no MIDI input found"),
+            CliError::Synth(err) => write!(f, "{}", err),
+            CliError::Wavetable(err) => write!(f, "{}", err),
+            CliError::UnknownWavetable(kind) => {
+                write!(f, "the patch does not say which file {} came from", kind)
+            }
+            CliError::Wav(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
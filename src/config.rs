@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::keyboard::KeyboardConfig;
+use crate::midi_learn::CcMapping;
+
+// settings of this installation rather than of a sound, the patch has the rest
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub keyboard: KeyboardConfig,
+    pub midi_mappings: Vec<CcMapping>,
+}
+
+impl Config {
+    // `$XDG_CONFIG_HOME/modelp/config.json`, or under `~/.config`
+    pub fn path() -> PathBuf {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .unwrap_or_default();
+        base.join("modelp").join("config.json")
+    }
+
+    // a missing file is not an error, it just has not been saved yet
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| ConfigError::InFile(path, Box::new(ConfigError::Json(err)))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(ConfigError::InFile(path, Box::new(ConfigError::Io(err)))),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(ConfigError::Io)?;
+        }
+        serde_json::to_string_pretty(self)
+            .map_err(ConfigError::Json)
+            .and_then(|text| std::fs::write(&path, text).map_err(ConfigError::Io))
+            .map_err(|err| ConfigError::InFile(path, Box::new(err)))
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    InFile(PathBuf, Box<ConfigError>),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Json(err) => write!(f, "{}", err),
+            ConfigError::InFile(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
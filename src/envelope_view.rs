@@ -0,0 +1,86 @@
+use egui::{Color32, Pos2, Sense, Stroke, Vec2};
+
+use crate::synth::{Envelope, VoiceState};
+
+const HEIGHT: f32 = 80.0;
+// of the width, the sustain has no duration of its own
+const SUSTAIN_WIDTH: f32 = 0.2;
+
+// the envelope as a curve over time, with a dot where each sounding voice is on it
+pub fn show(ui: &mut egui::Ui, envelope: &Envelope, voices: &[(VoiceState, f32)]) {
+    let size = Vec2::new(ui.available_width(), HEIGHT);
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    let rect = response.rect.shrink(4.0);
+    painter.rect_filled(response.rect, 4.0, Color32::from_gray(16));
+
+    let stages = Stages::new(envelope);
+    let point = |x: f32, level: f32| {
+        Pos2::new(
+            rect.left() + rect.width() * x,
+            rect.bottom() - rect.height() * level.clamp(0.0, 1.0),
+        )
+    };
+    let sustain = envelope.sustain;
+    let curve = vec![
+        point(0.0, 0.0),
+        point(stages.decay, 1.0),
+        point(stages.sustain, sustain),
+        point(stages.release, sustain),
+        point(1.0, 0.0),
+    ];
+    painter.add(egui::Shape::line(
+        curve,
+        Stroke::new(1.5, Color32::from_rgb(230, 180, 60)),
+    ));
+
+    for (state, volume) in voices {
+        if let Some(x) = stages.position(*state, *volume, sustain) {
+            painter.circle_filled(point(x, *volume), 4.0, Color32::WHITE);
+        }
+    }
+}
+
+// where each stage starts, as a fraction of the width
+// the stages take as long as they would on the audio thread: the decay always lasts
+// `decay_ms`, but the release only lasts `sustain * release_ms` as it falls at a fixed rate
+struct Stages {
+    decay: f32,
+    sustain: f32,
+    release: f32,
+}
+
+impl Stages {
+    fn new(envelope: &Envelope) -> Self {
+        let attack = envelope.attack_ms as f32;
+        let decay = envelope.decay_ms as f32;
+        let release = envelope.sustain * envelope.release_ms as f32;
+        let scale = (1.0 - SUSTAIN_WIDTH) / (attack + decay + release).max(1.0);
+
+        Self {
+            decay: attack * scale,
+            sustain: (attack + decay) * scale,
+            release: (attack + decay) * scale + SUSTAIN_WIDTH,
+        }
+    }
+
+    // voices released before reaching the sustain level sit at the start of the release
+    fn position(&self, state: VoiceState, volume: f32, sustain: f32) -> Option<f32> {
+        let along =
+            |from: f32, to: f32, fraction: f32| from + (to - from) * fraction.clamp(0.0, 1.0);
+        match state {
+            VoiceState::Idle => None,
+            VoiceState::Attacking(_) => Some(along(0.0, self.decay, volume)),
+            VoiceState::Decaying(_) => Some(along(
+                self.decay,
+                self.sustain,
+                (1.0 - volume) / (1.0 - sustain).max(f32::EPSILON),
+            )),
+            VoiceState::Sustaining(_) => Some(along(self.sustain, self.release, 0.5)),
+            VoiceState::Releasing(_) => Some(along(
+                self.release,
+                1.0,
+                (sustain - volume) / sustain.max(f32::EPSILON),
+            )),
+        }
+    }
+}
@@ -1,14 +1,68 @@
+use std::sync::Arc;
+
 use crate::midi::MidiNote;
-use crate::synth::WavetableKind;
+use crate::synth::{
+    ChorusMode, DelayTime, DriveCurve, Interpolation, KeyboardMapping, ModSource, MtsTable,
+    Oversampling, Scale, Transpose, UnisonPhase, VoiceMode, VoiceStealing, WavetableBank,
+    WavetableKind,
+};
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+// the MTS tables make it large, but a boxed one would be freed on the audio thread
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
 pub enum Event {
-    NoteOn(MidiNote),
+    // velocity from 1 to 127
+    NoteOn(MidiNote, u8),
     NoteOff(MidiNote),
-    OctaveUp,
-    OctaveDown,
+    SetVoiceMode(VoiceMode),
+    SetPolyphony(usize),
+    SetVoiceStealing(VoiceStealing),
+    SetUnisonVoices(usize),
+    SetUnisonDetune(f32),
+    SetUnisonSpread(f32),
+    SetUnisonPhase(UnisonPhase),
+    SetTranspose(Transpose),
+    // shared, the sender keeps them until the audio thread lets go, see `Synth::send_event`
+    SetScale(Arc<Scale>),
+    SetKeyboardMapping(Arc<KeyboardMapping>),
+    SetTuningReference(MidiNote, f32),
+    // the notes an MTS message changes, see `MtsTable::changes`
+    Retune(MtsTable),
+    SetRetuning(MtsTable),
     ChangeOscillator(WavetableKind),
+    // the bank with a newly imported table, see `Synth::add_wavetable`
+    SetWavetableBank(Arc<WavetableBank>),
+    SetInterpolation(Interpolation),
+    SetOscillatorPan(f32),
+    SetPosition(f32),
+    SetPositionModSource(ModSource),
+    SetPositionModAmount(f32),
+    SetLfoRate(f32),
+    SetTempo(f32),
+    SetDriveEnabled(bool),
+    SetDriveCurve(DriveCurve),
+    SetDriveGain(f32),
+    SetDriveFeedback(f32),
+    SetDriveOversampling(Oversampling),
+    SetChorusMode(ChorusMode),
+    SetChorusRate(f32),
+    SetChorusDepth(f32),
+    SetChorusVoices(usize),
+    SetChorusMix(f32),
+    SetDelayTime(DelayTime),
+    SetDelayFeedback(f32),
+    SetDelayTone(f32),
+    SetDelayPingPong(bool),
+    SetDelayMix(f32),
+    SetReverbSize(f32),
+    SetReverbDecay(f32),
+    SetReverbDamping(f32),
+    SetReverbPreDelay(f32),
+    SetReverbMix(f32),
     SetMaster(f32),
+    SetPan(f32),
+    SetWidth(f32),
+    SetCeiling(f32),
     SetAttackMs(u16),
     SetDecayMs(u16),
     SetSustain(f32),
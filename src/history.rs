@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+// how long the state has to stay the same before it becomes an undo step, so that a
+// scroll or a sweep on a controller is undone in one go
+const SETTLE: Duration = Duration::from_millis(300);
+const MAX_STEPS: usize = 100;
+
+// undo and redo over snapshots of the whole state, taken every frame
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    // the state the last step left
+    current: T,
+    // the state seen on the last frame, maybe not settled yet
+    latest: T,
+    changed_at: Instant,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    pub fn new(state: T) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            current: state.clone(),
+            latest: state,
+            changed_at: Instant::now(),
+        }
+    }
+
+    // `editing` holds the step open, while a drag is going on for instance
+    pub fn track(&mut self, state: T, editing: bool) {
+        if state != self.latest {
+            self.latest = state;
+            self.changed_at = Instant::now();
+        }
+        if !editing && self.changed_at.elapsed() >= SETTLE {
+            self.commit();
+        }
+    }
+
+    fn commit(&mut self) {
+        if self.latest == self.current {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.current, self.latest.clone());
+        self.undo.push(previous);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.latest != self.current
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // the state to go back to
+    pub fn undo(&mut self) -> Option<T> {
+        // a change that has not settled yet is undone first
+        self.commit();
+        let state = self.undo.pop()?;
+        self.redo.push(std::mem::replace(&mut self.current, state));
+        self.latest = self.current.clone();
+        Some(self.current.clone())
+    }
+
+    pub fn redo(&mut self) -> Option<T> {
+        if self.latest != self.current {
+            // edited since the last undo, there is nothing left to redo
+            self.commit();
+            return None;
+        }
+        let state = self.redo.pop()?;
+        self.undo.push(std::mem::replace(&mut self.current, state));
+        self.latest = self.current.clone();
+        Some(self.current.clone())
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, HashMap};
+
+use egui::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::midi::MidiNote;
+
+// the computer keyboard as a musical keyboard
+// the presets are described by physical position, the rows hold what each layout
+// reports for the keys of a US keyboard, `None` where egui has no `Key` for it
+
+// 1 2 3 4 5 6 7 8 9 0 - =
+type NumberRow = [Option<Key>; 12];
+// Q W E R T Y U I O P [ ]
+type TopRow = [Option<Key>; 12];
+// A S D F G H J K L ; '
+type HomeRow = [Option<Key>; 11];
+// Z X C V B N M , . /
+type BottomRow = [Option<Key>; 10];
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum LayoutPreset {
+    #[default]
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+}
+
+impl LayoutPreset {
+    pub const ALL: [LayoutPreset; 4] = [
+        LayoutPreset::Qwerty,
+        LayoutPreset::Azerty,
+        LayoutPreset::Qwertz,
+        LayoutPreset::Dvorak,
+    ];
+
+    fn rows(&self) -> (NumberRow, TopRow, HomeRow, BottomRow) {
+        use Key::*;
+        match self {
+            LayoutPreset::Qwerty => (
+                [
+                    Some(Num1),
+                    Some(Num2),
+                    Some(Num3),
+                    Some(Num4),
+                    Some(Num5),
+                    Some(Num6),
+                    Some(Num7),
+                    Some(Num8),
+                    Some(Num9),
+                    Some(Num0),
+                    Some(Minus),
+                    Some(Equals),
+                ],
+                [
+                    Some(Q),
+                    Some(W),
+                    Some(E),
+                    Some(R),
+                    Some(T),
+                    Some(Y),
+                    Some(U),
+                    Some(I),
+                    Some(O),
+                    Some(P),
+                    Some(OpenBracket),
+                    Some(CloseBracket),
+                ],
+                [
+                    Some(A),
+                    Some(S),
+                    Some(D),
+                    Some(F),
+                    Some(G),
+                    Some(H),
+                    Some(J),
+                    Some(K),
+                    Some(L),
+                    Some(Semicolon),
+                    Some(Quote),
+                ],
+                [
+                    Some(Z),
+                    Some(X),
+                    Some(C),
+                    Some(V),
+                    Some(B),
+                    Some(N),
+                    Some(M),
+                    Some(Comma),
+                    Some(Period),
+                    Some(Slash),
+                ],
+            ),
+            // the digits need shift on AZERTY, only a few of the unshifted symbols have a `Key`
+            LayoutPreset::Azerty => (
+                [
+                    None,
+                    None,
+                    None,
+                    Some(Quote),
+                    None,
+                    Some(Minus),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(Equals),
+                ],
+                [
+                    Some(A),
+                    Some(Z),
+                    Some(E),
+                    Some(R),
+                    Some(T),
+                    Some(Y),
+                    Some(U),
+                    Some(I),
+                    Some(O),
+                    Some(P),
+                    None,
+                    None,
+                ],
+                [
+                    Some(Q),
+                    Some(S),
+                    Some(D),
+                    Some(F),
+                    Some(G),
+                    Some(H),
+                    Some(J),
+                    Some(K),
+                    Some(L),
+                    Some(M),
+                    None,
+                ],
+                [
+                    Some(W),
+                    Some(X),
+                    Some(C),
+                    Some(V),
+                    Some(B),
+                    Some(N),
+                    Some(Comma),
+                    Some(Semicolon),
+                    Some(Colon),
+                    Some(Exclamationmark),
+                ],
+            ),
+            LayoutPreset::Qwertz => (
+                [
+                    Some(Num1),
+                    Some(Num2),
+                    Some(Num3),
+                    Some(Num4),
+                    Some(Num5),
+                    Some(Num6),
+                    Some(Num7),
+                    Some(Num8),
+                    Some(Num9),
+                    Some(Num0),
+                    None,
+                    None,
+                ],
+                [
+                    Some(Q),
+                    Some(W),
+                    Some(E),
+                    Some(R),
+                    Some(T),
+                    Some(Z),
+                    Some(U),
+                    Some(I),
+                    Some(O),
+                    Some(P),
+                    None,
+                    Some(Plus),
+                ],
+                [
+                    Some(A),
+                    Some(S),
+                    Some(D),
+                    Some(F),
+                    Some(G),
+                    Some(H),
+                    Some(J),
+                    Some(K),
+                    Some(L),
+                    None,
+                    None,
+                ],
+                [
+                    Some(Y),
+                    Some(X),
+                    Some(C),
+                    Some(V),
+                    Some(B),
+                    Some(N),
+                    Some(M),
+                    Some(Comma),
+                    Some(Period),
+                    Some(Minus),
+                ],
+            ),
+            LayoutPreset::Dvorak => (
+                [
+                    Some(Num1),
+                    Some(Num2),
+                    Some(Num3),
+                    Some(Num4),
+                    Some(Num5),
+                    Some(Num6),
+                    Some(Num7),
+                    Some(Num8),
+                    Some(Num9),
+                    Some(Num0),
+                    Some(OpenBracket),
+                    Some(CloseBracket),
+                ],
+                [
+                    Some(Quote),
+                    Some(Comma),
+                    Some(Period),
+                    Some(P),
+                    Some(Y),
+                    Some(F),
+                    Some(G),
+                    Some(C),
+                    Some(R),
+                    Some(L),
+                    Some(Slash),
+                    Some(Equals),
+                ],
+                [
+                    Some(A),
+                    Some(O),
+                    Some(E),
+                    Some(U),
+                    Some(I),
+                    Some(D),
+                    Some(H),
+                    Some(T),
+                    Some(N),
+                    Some(S),
+                    Some(Minus),
+                ],
+                [
+                    Some(Semicolon),
+                    Some(Q),
+                    Some(J),
+                    Some(K),
+                    Some(X),
+                    Some(B),
+                    Some(M),
+                    Some(W),
+                    Some(V),
+                    Some(Z),
+                ],
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for LayoutPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            LayoutPreset::Qwerty => "QWERTY",
+            LayoutPreset::Azerty => "AZERTY",
+            LayoutPreset::Qwertz => "QWERTZ",
+            LayoutPreset::Dvorak => "Dvorak",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+// semitones above the root of the white keys, one per key along the row
+const WHITE_STEPS: [u8; 12] = [0, 2, 4, 5, 7, 9, 11, 12, 14, 16, 17, 19];
+// (position in the row, semitones above the root)
+// the keys of a row sit between those of the row below, except where the piano has no black key
+const PIANO_BLACK_STEPS: [(usize, u8); 7] =
+    [(1, 1), (2, 3), (4, 6), (5, 8), (6, 10), (7, 13), (8, 15)];
+const TRACKER_BLACK_STEPS: [(usize, u8); 8] = [
+    (1, 1),
+    (2, 3),
+    (4, 6),
+    (5, 8),
+    (6, 10),
+    (8, 13),
+    (9, 15),
+    (11, 18),
+];
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyboardConfig {
+    pub preset: LayoutPreset,
+    // tracker style: the bottom two rows play one octave and the top two the next,
+    // the octave moves with the arrow keys instead of the bottom row
+    pub two_rows: bool,
+    // key names and semitones above the root, replaces the preset's notes when set
+    pub custom: Option<BTreeMap<String, u8>>,
+}
+
+pub struct KeyMap {
+    // semitones above the keyboard's root note
+    notes: HashMap<Key, u8>,
+    pub octave_down: Key,
+    pub octave_up: Key,
+}
+
+impl KeyMap {
+    pub fn new(config: &KeyboardConfig) -> Self {
+        let (number, top, home, bottom) = config.preset.rows();
+        let mut notes = HashMap::new();
+        let mut bind = |row: &[Option<Key>], offsets: &mut dyn Iterator<Item = (usize, u8)>| {
+            for (position, offset) in offsets {
+                if let Some(Some(key)) = row.get(position) {
+                    notes.insert(*key, offset);
+                }
+            }
+        };
+        let whites = |octave: u8| {
+            WHITE_STEPS
+                .iter()
+                .enumerate()
+                .map(move |(i, step)| (i, step + octave))
+        };
+        let blacks = |octave: u8| {
+            TRACKER_BLACK_STEPS
+                .iter()
+                .map(move |(i, step)| (*i, step + octave))
+        };
+
+        let (octave_down, octave_up) = if config.two_rows {
+            bind(&bottom, &mut whites(0));
+            bind(&home, &mut blacks(0));
+            bind(&top, &mut whites(12));
+            bind(&number, &mut blacks(12));
+            (Key::ArrowDown, Key::ArrowUp)
+        } else {
+            bind(&home, &mut whites(0));
+            bind(&top, &mut PIANO_BLACK_STEPS.into_iter());
+            (
+                bottom[0].unwrap_or(Key::ArrowDown),
+                bottom[1].unwrap_or(Key::ArrowUp),
+            )
+        };
+
+        if let Some(custom) = &config.custom {
+            notes = custom
+                .iter()
+                .filter_map(|(name, offset)| Some((Key::from_name(name)?, *offset)))
+                .collect();
+        }
+
+        Self {
+            notes,
+            octave_down,
+            octave_up,
+        }
+    }
+
+    pub fn note(&self, key: Key, root: MidiNote) -> Option<MidiNote> {
+        self.notes.get(&key).map(|offset| root.offset_up(*offset))
+    }
+
+    // the highest note above the root
+    pub fn span(&self) -> u8 {
+        self.notes.values().copied().max().unwrap_or(0)
+    }
+
+    // sorted by note, for display
+    pub fn bindings(&self) -> Vec<(Key, u8)> {
+        let mut bindings: Vec<(Key, u8)> = self
+            .notes
+            .iter()
+            .map(|(key, offset)| (*key, *offset))
+            .collect();
+        bindings.sort_by_key(|(key, offset)| (*offset, key.name()));
+        bindings
+    }
+
+    // the octave keys cannot play notes
+    pub fn bind(&mut self, key: Key, offset: u8) -> bool {
+        if key == self.octave_down || key == self.octave_up {
+            return false;
+        }
+        self.notes.insert(key, offset);
+        true
+    }
+
+    pub fn unbind(&mut self, key: Key) {
+        self.notes.remove(&key);
+    }
+
+    pub fn to_custom(&self) -> BTreeMap<String, u8> {
+        self.notes
+            .iter()
+            .map(|(key, offset)| (key.name().to_string(), *offset))
+            .collect()
+    }
+}
@@ -0,0 +1,9 @@
+#![allow(dead_code)]
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+
+// the sound engine, the app and the benchmarks are built on it
+pub mod event;
+pub mod math;
+pub mod midi;
+pub mod synth;
@@ -2,209 +2,1879 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use egui::Key;
+use egui::{Key, KeyboardShortcut, Modifiers, Vec2};
 
-mod event;
-mod math;
-mod midi;
-mod synth;
+mod cli;
+mod config;
+mod envelope_view;
+mod history;
+mod keyboard;
+mod midi_file;
+mod midi_input;
+mod midi_learn;
+mod params;
+mod patch;
+mod piano;
+mod scope;
+mod script;
+mod spectrum;
+mod widgets;
 
+use modelp::{event, math, midi, synth};
+
+use crate::cli::{CliError, Command};
+use crate::config::Config;
 use crate::event::Event;
-use crate::midi::MidiNote;
+use crate::history::History;
+use crate::keyboard::{KeyMap, KeyboardConfig, LayoutPreset};
+use crate::midi::{MidiNote, DEFAULT_VELOCITY};
+use crate::midi_input::{ControlChange, MidiInput};
+use crate::midi_learn::MidiLearn;
+use crate::params::{choice, ParamId, BUILT_IN_WAVEFORMS};
+use crate::patch::Patch;
+use crate::piano::Piano;
+use crate::scope::Scope;
+use crate::spectrum::Spectrum;
 use crate::synth::Synth;
-use crate::synth::WavetableKind;
+use crate::synth::{
+    ChorusMode, ChorusParams, DelayParams, DelayTime, DriveCurve, DriveParams, Interpolation,
+    KeyboardMapping, ModSource, MtsMessage, MtsTable, NoteDivision, Oversampling, ReverbParams,
+    Scale, Transpose, UnisonPhase, VoiceMode, VoiceStealing, Wavetable, WavetableError,
+    WavetableKind, DEFAULT_CEILING_DB, DEFAULT_TEMPO_BPM, MAX_CHORUS_RATE_HZ, MAX_CHORUS_VOICES,
+    MAX_DECAY_S, MAX_DELAY_MS, MAX_DETUNE_CENTS, MAX_DRIVE_DB, MAX_FEEDBACK, MAX_PRE_DELAY_MS,
+    MAX_TEMPO_BPM, MAX_UNISON, MAX_VOICES, MAX_WIDTH, MIN_CEILING_DB, MIN_TEMPO_BPM,
+};
+use crate::widgets::{Knob, Toggle};
+
+// how long the clip indicator stays lit after the limiter stepped in
+const CLIP_HOLD: Duration = Duration::from_secs(1);
+
+// the computer keyboard spans a bit more than an octave from C of this octave
+const MAX_KEYBOARD_OCTAVE: u8 = 8;
 
 struct App {
     synth: Synth,
-    pressed_keys: HashSet<egui::Key>,
-    root_note: MidiNote,
+    // the note each held key started, so that it can be released after an octave change
+    pressed_keys: HashMap<egui::Key, MidiNote>,
+    // keys of consumed shortcuts and of typing in text fields, their release is not a note,
+    // an octave change or Escape either
+    ignored_keys: HashSet<egui::Key>,
+    keyboard_octave: u8,
+    config: Config,
+    config_error: Option<String>,
+    layout: KeyboardLayout,
+    midi_learn: MidiLearn,
+    // control changes from the MIDI input thread
+    controls_tx: mpsc::Sender<ControlChange>,
+    controls_rx: mpsc::Receiver<ControlChange>,
+    transpose: Transpose,
+    voice_mode: VoiceMode,
+    polyphony: usize,
+    voice_stealing: VoiceStealing,
+    unison_voices: usize,
+    unison_detune: f32,
+    unison_spread: f32,
+    unison_phase: UnisonPhase,
     current_wavetable: WavetableKind,
+    user_wavetables: Vec<UserWavetable>,
+    wavetable_path: String,
+    // decoded wavetables from the import threads
+    imports_tx: mpsc::Sender<(PathBuf, Result<Wavetable, WavetableError>)>,
+    imports_rx: mpsc::Receiver<(PathBuf, Result<Wavetable, WavetableError>)>,
+    importing: usize,
+    import_error: Option<String>,
+    interpolation: Interpolation,
+    oscillator_pan: f32,
+    position: f32,
+    position_mod_source: ModSource,
+    position_mod_amount: f32,
+    lfo_rate: f32,
+    tempo: f32,
+    drive: DriveParams,
+    chorus: ChorusParams,
+    delay: DelayParams,
+    reverb: ReverbParams,
     master_volume: f32,
+    pan: f32,
+    width: f32,
+    ceiling_db: f32,
+    // with a falloff, for the meter
+    output_peak: f32,
+    clipped_at: Option<Instant>,
+    piano: Piano,
+    scope: Scope,
+    spectrum: Spectrum,
+    tuning: Tuning,
     envelope: crate::synth::Envelope,
+    patch_path: String,
+    patch_error: Option<String>,
+    midi_input: MidiInput,
+    midi_port: Option<String>,
+    midi_error: Option<String>,
+    // the transpose is not saved with the patch but it can be undone all the same
+    history: History<(Patch, Transpose)>,
 }
 
 impl Default for App {
     fn default() -> Self {
         let synth = Synth::new();
-        let pressed_keys: HashSet<egui::Key> = HashSet::new();
-        let root_note = MidiNote::c(2);
+        let pressed_keys: HashMap<egui::Key, MidiNote> = HashMap::new();
+        let keyboard_octave: u8 = 2;
         let current_wavetable = WavetableKind::Triangle;
         let envelope = crate::synth::Envelope::default();
+        let tuning = Tuning::default();
+        let midi_input = MidiInput::new(tuning.retuning.clone());
+        let (config, config_error) = match Config::load() {
+            Ok(config) => (config, None),
+            Err(err) => (Config::default(), Some(err.to_string())),
+        };
+        let (controls_tx, controls_rx) = mpsc::channel();
+        let (imports_tx, imports_rx) = mpsc::channel();
 
-        Self {
+        let mut app = Self {
             synth,
             pressed_keys,
-            root_note,
+            ignored_keys: HashSet::new(),
+            keyboard_octave,
+            layout: KeyboardLayout::new(config.keyboard.clone()),
+            midi_learn: MidiLearn::new(config.midi_mappings.clone()),
+            config,
+            config_error,
+            controls_tx,
+            controls_rx,
+            transpose: Transpose::default(),
+            voice_mode: VoiceMode::default(),
+            polyphony: 8,
+            voice_stealing: VoiceStealing::default(),
+            unison_voices: 1,
+            unison_detune: 0.0,
+            unison_spread: 0.0,
+            unison_phase: UnisonPhase::default(),
             current_wavetable,
+            user_wavetables: Vec::new(),
+            wavetable_path: String::new(),
+            imports_tx,
+            imports_rx,
+            importing: 0,
+            import_error: None,
+            interpolation: Interpolation::default(),
+            oscillator_pan: 0.0,
+            position: 0.0,
+            position_mod_source: ModSource::default(),
+            position_mod_amount: 0.0,
+            lfo_rate: 1.0,
+            tempo: DEFAULT_TEMPO_BPM,
+            drive: DriveParams::default(),
+            chorus: ChorusParams::default(),
+            delay: DelayParams::default(),
+            reverb: ReverbParams::default(),
             master_volume: 0.7,
+            pan: 0.0,
+            width: 1.0,
+            ceiling_db: DEFAULT_CEILING_DB,
+            output_peak: 0.0,
+            clipped_at: None,
+            piano: Piano::default(),
+            scope: Scope::default(),
+            spectrum: Spectrum::default(),
+            tuning,
             envelope,
+            patch_path: String::from("patch.json"),
+            patch_error: None,
+            midi_input,
+            midi_port: None,
+            midi_error: None,
+            history: History::new((Patch::default(), Transpose::default())),
+        };
+        app.history = History::new(app.snapshot());
+
+        app
+    }
+}
+
+// an imported wavetable, patches that use it keep the path
+struct UserWavetable {
+    kind: WavetableKind,
+    name: String,
+    path: PathBuf,
+}
+
+// the computer keyboard mapping, saved in the config rather than in the patch
+struct KeyboardLayout {
+    keyboard: KeyboardConfig,
+    keymap: KeyMap,
+    window_open: bool,
+    // while set, the next key pressed is bound to this many semitones above the root
+    learning: Option<u8>,
+    learn_offset: u8,
+    // set when the config needs saving
+    changed: bool,
+}
+
+impl KeyboardLayout {
+    fn new(keyboard: KeyboardConfig) -> Self {
+        Self {
+            keymap: KeyMap::new(&keyboard),
+            keyboard,
+            window_open: false,
+            learning: None,
+            learn_offset: 0,
+            changed: false,
+        }
+    }
+
+    fn set(&mut self, keyboard: KeyboardConfig) {
+        self.keymap = KeyMap::new(&keyboard);
+        self.keyboard = keyboard;
+        self.changed = true;
+    }
+
+    // edits turn the preset into a custom mapping
+    fn customize(&mut self, edit: impl FnOnce(&mut KeyMap)) {
+        edit(&mut self.keymap);
+        let keyboard = KeyboardConfig {
+            custom: Some(self.keymap.to_custom()),
+            ..self.keyboard.clone()
+        };
+        self.set(keyboard);
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui) {
+        let mut keyboard = self.keyboard.clone();
+        let mut changed = false;
+        egui::ComboBox::from_label("Layout")
+            .selected_text(format!("{}", keyboard.preset))
+            .show_ui(ui, |ui| {
+                for preset in LayoutPreset::ALL {
+                    changed |= ui
+                        .selectable_value(&mut keyboard.preset, preset, format!("{preset}"))
+                        .clicked();
+                }
+            });
+        changed |= ui
+            .checkbox(&mut keyboard.two_rows, "Two rows (two octaves)")
+            .changed();
+        if changed {
+            keyboard.custom = None;
+            self.set(keyboard);
+        }
+        ui.label(format!(
+            "Octave down/up: {} / {}",
+            self.keymap.octave_down.name(),
+            self.keymap.octave_up.name()
+        ));
+        if self.keyboard.custom.is_some() && ui.button("Reset to preset").clicked() {
+            let keyboard = KeyboardConfig {
+                custom: None,
+                ..self.keyboard.clone()
+            };
+            self.set(keyboard);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.learn_offset)
+                    .range(0..=36)
+                    .suffix(" st"),
+            );
+            if self.learning.is_some() {
+                ui.label("Press a key…");
+                if ui.button("Cancel").clicked() {
+                    self.learning = None;
+                }
+            } else if ui.button("Bind a key").clicked() {
+                self.learning = Some(self.learn_offset);
+            }
+        });
+        let mut unbind = None;
+        egui::ScrollArea::vertical()
+            .max_height(240.0)
+            .show(ui, |ui| {
+                egui::Grid::new("Bindings").striped(true).show(ui, |ui| {
+                    for (key, offset) in self.keymap.bindings() {
+                        ui.label(key.name());
+                        ui.label(format!("+{offset} st"));
+                        if ui.small_button("Remove").clicked() {
+                            unbind = Some(key);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        if let Some(key) = unbind {
+            self.customize(|keymap| keymap.unbind(key));
+        }
+    }
+}
+
+// GUI side of the tuner, the files are parsed here and sent to the audio thread
+struct Tuning {
+    scale: Scale,
+    mapping: KeyboardMapping,
+    // written by the MIDI input thread
+    retuning: Arc<Mutex<MtsTable>>,
+    scale_path: String,
+    mapping_path: String,
+    sysex_path: String,
+    error: Option<String>,
+    window_open: bool,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            scale: Scale::equal_temperament(12),
+            mapping: KeyboardMapping::default(),
+            retuning: Arc::new(Mutex::new(MtsTable::default())),
+            scale_path: String::new(),
+            mapping_path: String::new(),
+            sysex_path: String::new(),
+            error: None,
+            window_open: false,
         }
     }
 }
 
-fn main() -> Result<(), eframe::Error> {
+impl App {
+    fn root_note(&self) -> MidiNote {
+        MidiNote::c(self.keyboard_octave)
+    }
+
+    fn set_transpose(&mut self, transpose: Transpose) {
+        self.transpose = transpose.clamped();
+        self.synth.send_event(Event::SetTranspose(self.transpose));
+    }
+
+    fn load_scale(&mut self) {
+        match Scale::from_disk(self.tuning.scale_path.trim()) {
+            Ok(scale) => {
+                self.tuning.scale = scale.clone();
+                self.synth.send_event(Event::SetScale(Arc::new(scale)));
+                self.tuning.error = None;
+            }
+            Err(err) => self.tuning.error = Some(err.to_string()),
+        }
+    }
+
+    fn load_mapping(&mut self) {
+        match KeyboardMapping::from_disk(self.tuning.mapping_path.trim()) {
+            Ok(mapping) => {
+                self.tuning.mapping = mapping.clone();
+                self.synth
+                    .send_event(Event::SetKeyboardMapping(Arc::new(mapping)));
+                self.tuning.error = None;
+            }
+            Err(err) => self.tuning.error = Some(err.to_string()),
+        }
+    }
+
+    // every MTS message found in the file is applied in order
+    fn load_sysex(&mut self) {
+        let path = self.tuning.sysex_path.trim().to_string();
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                self.tuning.error = Some(format!("{path}: {err}"));
+                return;
+            }
+        };
+        for sysex in bytes.split_inclusive(|byte| *byte == 0xF7) {
+            let Some(start) = sysex.iter().position(|byte| *byte == 0xF0) else {
+                continue;
+            };
+            match MtsMessage::parse(&sysex[start..]) {
+                Ok(message) => {
+                    let changes = MtsTable::changes(&message);
+                    self.tuning.retuning.lock().unwrap().merge(&changes);
+                    self.synth.send_event(Event::Retune(changes));
+                    self.tuning.error = None;
+                }
+                Err(err) => self.tuning.error = Some(format!("{path}: {err}")),
+            }
+        }
+    }
+
+    fn reset_tuning(&mut self) {
+        self.tuning.scale = Scale::equal_temperament(12);
+        self.tuning.mapping = KeyboardMapping::default();
+        *self.tuning.retuning.lock().unwrap() = MtsTable::default();
+        self.tuning.error = None;
+        self.synth
+            .send_event(Event::SetScale(Arc::new(self.tuning.scale.clone())));
+        self.synth.send_event(Event::SetKeyboardMapping(Arc::new(
+            self.tuning.mapping.clone(),
+        )));
+        self.synth
+            .send_event(Event::SetRetuning(MtsTable::default()));
+    }
+
+    fn patch(&self) -> Patch {
+        Patch {
+            scale: self.tuning.scale.clone(),
+            mapping: self.tuning.mapping.clone(),
+            retuning: self.tuning.retuning.lock().unwrap().clone(),
+            ..self.parameters()
+        }
+    }
+
+    // the patch without the tuning, which comes from files and the MIDI input rather than
+    // from the controls and is left out of the undo steps
+    fn parameters(&self) -> Patch {
+        Patch {
+            voice_mode: self.voice_mode,
+            polyphony: self.polyphony,
+            voice_stealing: self.voice_stealing,
+            unison_voices: self.unison_voices,
+            unison_detune: self.unison_detune,
+            unison_spread: self.unison_spread,
+            unison_phase: self.unison_phase,
+            wavetable: self.current_wavetable,
+            wavetable_file: self
+                .user_wavetables
+                .iter()
+                .find(|wavetable| wavetable.kind == self.current_wavetable)
+                .map(|wavetable| wavetable.path.clone()),
+            interpolation: self.interpolation,
+            oscillator_pan: self.oscillator_pan,
+            position: self.position,
+            position_mod_source: self.position_mod_source,
+            position_mod_amount: self.position_mod_amount,
+            lfo_rate: self.lfo_rate,
+            tempo: self.tempo,
+            drive: self.drive,
+            chorus: self.chorus,
+            delay: self.delay,
+            reverb: self.reverb,
+            master: self.master_volume,
+            pan: self.pan,
+            width: self.width,
+            ceiling_db: self.ceiling_db,
+            envelope: self.envelope,
+            ..Patch::default()
+        }
+    }
+
+    fn apply_patch(&mut self, mut patch: Patch) {
+        self.find_wavetable(&mut patch);
+        for event in patch.events() {
+            self.synth.send_event(event);
+        }
+        self.load_fields(patch);
+    }
+
+    // a user wavetable is looked up by its file, and imported again when it is not there
+    // the oscillator plays a triangle until the import is done
+    fn find_wavetable(&mut self, patch: &mut Patch) {
+        if !matches!(patch.wavetable, WavetableKind::User(_)) {
+            return;
+        }
+        let imported = patch.wavetable_file.as_ref().and_then(|path| {
+            self.user_wavetables
+                .iter()
+                .find(|wavetable| &wavetable.path == path)
+        });
+        match (imported, &patch.wavetable_file) {
+            (Some(wavetable), _) => patch.wavetable = wavetable.kind,
+            (None, Some(path)) => {
+                self.import_wavetable(path.clone());
+                patch.wavetable = WavetableKind::Triangle;
+            }
+            (None, None) => {
+                self.patch_error = Some(format!(
+                    "the patch does not say which file {} came from, playing {} instead",
+                    patch.wavetable,
+                    WavetableKind::Triangle
+                ));
+                patch.wavetable = WavetableKind::Triangle;
+            }
+        }
+    }
+
+    // the GUI side only, the audio thread is left as it is
+    fn load_fields(&mut self, patch: Patch) {
+        self.voice_mode = patch.voice_mode;
+        self.polyphony = patch.polyphony;
+        self.voice_stealing = patch.voice_stealing;
+        self.unison_voices = patch.unison_voices;
+        self.unison_detune = patch.unison_detune;
+        self.unison_spread = patch.unison_spread;
+        self.unison_phase = patch.unison_phase;
+        self.current_wavetable = patch.wavetable;
+        self.interpolation = patch.interpolation;
+        self.oscillator_pan = patch.oscillator_pan;
+        self.position = patch.position;
+        self.position_mod_source = patch.position_mod_source;
+        self.position_mod_amount = patch.position_mod_amount;
+        self.lfo_rate = patch.lfo_rate;
+        self.tempo = patch.tempo;
+        self.drive = patch.drive;
+        self.chorus = patch.chorus;
+        self.delay = patch.delay;
+        self.reverb = patch.reverb;
+        self.master_volume = patch.master;
+        self.pan = patch.pan;
+        self.width = patch.width;
+        self.ceiling_db = patch.ceiling_db;
+        self.envelope = patch.envelope;
+        self.tuning.scale = patch.scale;
+        self.tuning.mapping = patch.mapping;
+        *self.tuning.retuning.lock().unwrap() = patch.retuning;
+    }
+
+    fn snapshot(&self) -> (Patch, Transpose) {
+        (self.parameters(), self.transpose)
+    }
+
+    fn undo(&mut self) {
+        if let Some((patch, transpose)) = self.history.undo() {
+            self.apply_step(patch, transpose);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some((patch, transpose)) = self.history.redo() {
+            self.apply_step(patch, transpose);
+        }
+    }
+
+    // the tuning stays as it is, it is not part of the step
+    fn apply_step(&mut self, parameters: Patch, transpose: Transpose) {
+        let patch = Patch {
+            scale: self.tuning.scale.clone(),
+            mapping: self.tuning.mapping.clone(),
+            retuning: self.tuning.retuning.lock().unwrap().clone(),
+            ..parameters
+        };
+        self.apply_patch(patch);
+        self.set_transpose(transpose);
+    }
+
+    fn save_patch(&mut self) {
+        self.patch_error = self
+            .patch()
+            .save(self.patch_path.trim())
+            .err()
+            .map(|err| err.to_string());
+    }
+
+    fn load_patch(&mut self) {
+        match Patch::from_disk(self.patch_path.trim()) {
+            Ok(patch) => {
+                self.patch_error = None;
+                self.apply_patch(patch);
+            }
+            Err(err) => self.patch_error = Some(err.to_string()),
+        }
+    }
+
+    // control changes wake the GUI up, it is the one that knows what they are mapped to
+    fn connect_midi(&mut self, ctx: &egui::Context, port_name: String) {
+        let controls_tx = self.controls_tx.clone();
+        let ctx = ctx.clone();
+        let controls = move |cc| {
+            let _ = controls_tx.send(cc);
+            ctx.request_repaint();
+        };
+        match self
+            .midi_input
+            .connect(&port_name, self.synth.event_sender(), controls)
+        {
+            Ok(()) => {
+                self.midi_port = Some(port_name);
+                self.midi_error = None;
+            }
+            Err(err) => {
+                self.midi_port = None;
+                self.midi_error = Some(err.to_string());
+            }
+        }
+    }
+
+    fn save_config(&mut self) {
+        self.config.keyboard = self.layout.keyboard.clone();
+        self.config.midi_mappings = self.midi_learn.mappings.clone();
+        self.layout.changed = false;
+        self.midi_learn.changed = false;
+        self.config_error = self.config.save().err().map(|err| err.to_string());
+    }
+
+    // the wavetables the oscillator bank offers, in order
+    fn wavetables(&self) -> Vec<(WavetableKind, String)> {
+        BUILT_IN_WAVEFORMS
+            .map(|kind| (kind, format!("{kind}")))
+            .into_iter()
+            .chain(
+                self.user_wavetables
+                    .iter()
+                    .map(|wavetable| (wavetable.kind, wavetable.name.clone())),
+            )
+            .collect()
+    }
+
+    // `position` goes from 0 to 1 across the parameter's range
+    fn set_param(&mut self, param: ParamId, position: f32) {
+        let value = param.range().value(position);
+        match param {
+            ParamId::Octave | ParamId::Semitones | ParamId::MasterTune => {
+                let mut transpose = self.transpose;
+                match param {
+                    ParamId::Octave => transpose.octaves = value as i8,
+                    ParamId::Semitones => transpose.semitones = value as i8,
+                    _ => transpose.cents = value,
+                }
+                self.set_transpose(transpose);
+            }
+            ParamId::Waveform => {
+                let wavetables = self.wavetables();
+                self.current_wavetable = wavetables[choice(value, wavetables.len())].0;
+                self.synth
+                    .send_event(Event::ChangeOscillator(self.current_wavetable));
+            }
+            _ => {
+                let mut patch = self.patch();
+                if let Some(event) = param.set(&mut patch, value) {
+                    self.synth.send_event(event);
+                    self.load_fields(patch);
+                }
+            }
+        }
+    }
+
+    // decoding and resampling a long cycle takes a while, it is done on a thread of its own
+    // and picked up by the next frame, which is at most the meter's refresh away
+    fn import_wavetable(&mut self, path: PathBuf) {
+        // absolute, for patches to find it from anywhere
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+        let imports_tx = self.imports_tx.clone();
+        self.importing += 1;
+        std::thread::spawn(move || {
+            let result = Wavetable::from_disk(&path);
+            let _ = imports_tx.send((path, result));
+        });
+    }
+
+    fn finish_import(&mut self, path: &Path, result: Result<Wavetable, WavetableError>) {
+        self.importing -= 1;
+        match result {
+            Ok(wavetable) => {
+                let kind = self.synth.add_wavetable(wavetable);
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| format!("{kind}"));
+                self.user_wavetables.push(UserWavetable {
+                    kind,
+                    name,
+                    path: path.to_path_buf(),
+                });
+                self.current_wavetable = kind;
+                self.synth.send_event(Event::ChangeOscillator(kind));
+                self.import_error = None;
+            }
+            Err(err) => self.import_error = Some(err.to_string()),
+        }
+    }
+}
+
+fn main() -> ExitCode {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
-    let options = eframe::NativeOptions::default();
-    eframe::run_native("ModelP", options, Box::new(|_cc| Ok(Box::<App>::default())))
+    let result = Command::parse(std::env::args().skip(1)).and_then(|command| match command {
+        Command::Gui => {
+            let options = eframe::NativeOptions::default();
+            eframe::run_native("ModelP", options, Box::new(|_cc| Ok(Box::<App>::default())))
+                .map_err(CliError::Gui)
+        }
+        command => cli::run(command),
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ModelP: {err}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::SidePanel::left("OscillatorBank").show(ctx, |ui| {
-            ui.heading("Oscillator Bank");
-            ui.vertical(|ui| {
-                let kind = WavetableKind::Triangle;
+        let (peak, clipped) = self.synth.meter().take();
+        self.output_peak = peak.max(0.9 * self.output_peak);
+        if clipped {
+            self.clipped_at = Some(Instant::now());
+        }
+        // the meter moves even when nothing else does
+        ctx.request_repaint_after(Duration::from_millis(50));
+
+        let controls: Vec<ControlChange> = self.controls_rx.try_iter().collect();
+        for cc in controls {
+            for (param, position) in self.midi_learn.control_change(cc) {
+                self.set_param(param, position);
+            }
+        }
+
+        // taken before the panels are drawn, a field that lets go of the focus on Escape still
+        // had it when the key went down
+        let typing = ctx.wants_keyboard_input();
+        // text fields keep their own undo
+        if !typing {
+            // checked first, the plain shortcut also matches with shift held
+            let redo = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z);
+            let undo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+            if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+                self.ignored_keys.insert(Key::Z);
+                self.redo();
+            } else if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+                self.ignored_keys.insert(Key::Z);
+                self.undo();
+            }
+        }
+
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        for path in dropped_files.into_iter().filter_map(|file| file.path) {
+            self.import_wavetable(path);
+        }
+        let imports: Vec<_> = self.imports_rx.try_iter().collect();
+        for (path, result) in imports {
+            self.finish_import(&path, result);
+        }
+
+        egui::TopBottomPanel::top("Patch").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Patch");
+                ui.text_edit_singleline(&mut self.patch_path);
+                if ui.button("Save").clicked() {
+                    self.save_patch();
+                }
+                if ui.button("Load").clicked() {
+                    self.load_patch();
+                }
                 if ui
-                    .radio_value(&mut self.current_wavetable, kind, format!("{kind}"))
+                    .add_enabled(self.history.can_undo(), egui::Button::new("Undo"))
                     .clicked()
                 {
-                    self.synth.send_event(Event::ChangeOscillator(kind));
+                    self.undo();
                 }
-                let kind = WavetableKind::Square;
                 if ui
-                    .radio_value(
-                        &mut self.current_wavetable,
-                        kind,
-                        format!("{kind} (you should lower the master way down)"),
-                    )
+                    .add_enabled(self.history.can_redo(), egui::Button::new("Redo"))
                     .clicked()
                 {
-                    self.synth.send_event(Event::ChangeOscillator(kind));
+                    self.redo();
+                }
+
+                ui.separator();
+                let selected = self.midi_port.as_deref().unwrap_or("None").to_string();
+                let mut port_to_connect: Option<String> = None;
+                egui::ComboBox::from_label("MIDI input")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        if ui
+                            .selectable_label(self.midi_port.is_none(), "None")
+                            .clicked()
+                        {
+                            self.midi_input.disconnect();
+                            self.midi_port = None;
+                        }
+                        for port in MidiInput::ports() {
+                            let is_selected = self.midi_port.as_ref() == Some(&port);
+                            if ui.selectable_label(is_selected, &port).clicked() {
+                                port_to_connect = Some(port);
+                            }
+                        }
+                    });
+                if let Some(port) = port_to_connect {
+                    self.connect_midi(ctx, port);
                 }
             });
-            ui.end_row();
+            ui.horizontal(|ui| {
+                let root = self.root_note();
+                let keymap = &self.layout.keymap;
+                ui.label(format!(
+                    "Keyboard {}–{} ({}/{})",
+                    root,
+                    root.offset_up(keymap.span()),
+                    keymap.octave_down.name(),
+                    keymap.octave_up.name()
+                ));
+                if ui.button("Layout…").clicked() {
+                    self.layout.window_open = true;
+                }
+                ui.separator();
+                ui.label(format!("Transpose {}", self.transpose));
+            });
+            let errors = [&self.patch_error, &self.midi_error, &self.config_error];
+            for error in errors.into_iter().flatten() {
+                ui.colored_label(egui::Color32::RED, error);
+            }
         });
 
-        egui::SidePanel::right("Output").show(ctx, |ui| {
-            ui.heading("Output");
-            if ui
-                .add(egui::Slider::new(&mut self.master_volume, 0.0..=1.0).text("Master"))
-                .dragged()
-            {
-                self.synth.send_event(Event::SetMaster(self.master_volume));
-            }
+        egui::SidePanel::right("Effects").show(ctx, |ui| {
+            ui.heading("Effects");
+            self.show_effects(ui);
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {});
+        egui::TopBottomPanel::bottom("Keyboard").show(ctx, |ui| {
+            let root = self.root_note();
+            let range = (root, root.offset_up(self.layout.keymap.span()));
+            ui.label(format!("Computer keyboard {}–{}", range.0, range.1));
+            let voices = self.synth.voice_monitor().load();
+            for event in self.piano.show(ui, &voices, range) {
+                self.synth.send_event(event);
+            }
+        });
 
-        egui::TopBottomPanel::bottom("Amp").show(ctx, |ui| {
-            ui.heading("Loudness Contour");
+        egui::TopBottomPanel::bottom("FrontPanel").show(ctx, |ui| {
             if ctx.input(|i| i.viewport().close_requested()) {
                 ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
             }
 
-            if ui
-                .add(
-                    egui::Slider::new(&mut self.envelope.attack_ms, 5..=10000)
-                        .logarithmic(true)
-                        .text("Attack (ms)"),
+            egui::ScrollArea::horizontal().show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    section(ui, "Controllers", |ui| self.show_controllers(ui));
+                    section(ui, "Oscillator Bank", |ui| self.show_oscillator_bank(ui));
+                    section(ui, "Mixer", |ui| self.show_mixer(ui));
+                    section(ui, "Modifiers", |ui| self.show_modifiers(ui));
+                    section(ui, "Output", |ui| self.show_output(ui));
+                });
+            });
+
+            self.handle_keys(ui.ctx(), typing);
+        });
+
+        let mut layout_open = self.layout.window_open;
+        egui::Window::new("Keyboard layout")
+            .open(&mut layout_open)
+            .show(ctx, |ui| self.layout.show(ui));
+        self.layout.window_open = layout_open;
+        if self.layout.changed || self.midi_learn.changed {
+            self.save_config();
+        }
+        // a drag is one step, however long it goes on
+        let editing = ctx.input(|i| i.pointer.any_down());
+        self.history.track(self.snapshot(), editing);
+
+        let mut tuning_open = self.tuning.window_open;
+        egui::Window::new("Tuning")
+            .open(&mut tuning_open)
+            .show(ctx, |ui| self.show_tuning(ui));
+        self.tuning.window_open = tuning_open;
+
+        // last, it takes whatever space the panels left
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let sample_rate = self.synth.sample_rate();
+            let size = Vec2::new(ui.available_width(), 0.5 * ui.available_height());
+            ui.allocate_ui(size, |ui| {
+                ui.heading("Scope");
+                self.scope.show(ui, self.synth.monitor(), sample_rate);
+            });
+            ui.heading("Spectrum");
+            self.spectrum.show(ui, self.synth.monitor(), sample_rate);
+        });
+    }
+}
+
+// a titled group of controls on the front panel
+fn section(ui: &mut egui::Ui, title: &str, add_contents: impl FnOnce(&mut egui::Ui)) {
+    ui.group(|ui| {
+        ui.vertical(|ui| {
+            ui.label(egui::RichText::new(title).strong());
+            add_contents(ui);
+        });
+    });
+}
+
+// the front panel, laid out after the Model D's
+// double-clicking a knob brings it back to the value of a new patch
+impl App {
+    fn show_controllers(&mut self, ui: &mut egui::Ui) {
+        let defaults = Patch::default();
+        let mut transpose = self.transpose;
+        ui.horizontal(|ui| {
+            self.midi_learn.menu(
+                ui.add(
+                    Knob::new(
+                        "Tune",
+                        &mut transpose.cents,
+                        -Transpose::MAX_CENTS..=Transpose::MAX_CENTS,
+                    )
+                    .default_value(0.0)
+                    .decimals(0)
+                    .suffix(" ct"),
+                ),
+                ParamId::MasterTune,
+            );
+            self.midi_learn.menu(
+                ui.add(
+                    Knob::new(
+                        "Semitones",
+                        &mut transpose.semitones,
+                        -Transpose::MAX_SEMITONES..=Transpose::MAX_SEMITONES,
+                    )
+                    .default_value(0),
+                ),
+                ParamId::Semitones,
+            );
+            ui.vertical(|ui| {
+                ui.label("Octave");
+                ui.horizontal(|ui| {
+                    let down = ui.button("−");
+                    if down.clicked() {
+                        transpose.octave_down();
+                    }
+                    ui.label(format!("{:+}", transpose.octaves));
+                    let up = ui.button("+");
+                    if up.clicked() {
+                        transpose.octave_up();
+                    }
+                    self.midi_learn.menu(down | up, ParamId::Octave);
+                });
+                if ui.button("Tuning…").clicked() {
+                    self.tuning.window_open = true;
+                }
+            });
+        });
+        if transpose != self.transpose {
+            self.set_transpose(transpose);
+        }
+
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("LFO rate", &mut self.lfo_rate, 0.01..=20.0)
+                            .default_value(defaults.lfo_rate)
+                            .logarithmic(true)
+                            .suffix(" Hz"),
+                    ),
+                    ParamId::LfoRate,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetLfoRate(self.lfo_rate));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Mod amount", &mut self.position_mod_amount, -1.0..=1.0)
+                            .default_value(defaults.position_mod_amount),
+                    ),
+                    ParamId::PositionModAmount,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetPositionModAmount(self.position_mod_amount));
+            }
+            let mut poly = self.voice_mode == VoiceMode::Poly;
+            if self
+                .midi_learn
+                .menu(ui.add(Toggle::new("Poly", &mut poly)), ParamId::VoiceMode)
+                .changed()
+            {
+                self.voice_mode = if poly {
+                    VoiceMode::Poly
+                } else {
+                    VoiceMode::Mono
+                };
+                self.synth.send_event(Event::SetVoiceMode(self.voice_mode));
+            }
+            ui.add_enabled_ui(poly, |ui| {
+                if self
+                    .midi_learn
+                    .menu(
+                        ui.add(
+                            Knob::new("Voices", &mut self.polyphony, 1..=MAX_VOICES)
+                                .default_value(defaults.polyphony),
+                        ),
+                        ParamId::Polyphony,
+                    )
+                    .changed()
+                {
+                    self.synth.send_event(Event::SetPolyphony(self.polyphony));
+                }
+            });
+        });
+
+        let combo = egui::ComboBox::from_label("Mod source")
+            .selected_text(format!("{}", self.position_mod_source))
+            .show_ui(ui, |ui| {
+                for source in ModSource::ALL {
+                    if ui
+                        .selectable_value(
+                            &mut self.position_mod_source,
+                            source,
+                            format!("{source}"),
+                        )
+                        .clicked()
+                    {
+                        self.synth.send_event(Event::SetPositionModSource(source));
+                    }
+                }
+            });
+        self.midi_learn
+            .menu(combo.response, ParamId::PositionModSource);
+        ui.add_enabled_ui(self.voice_mode == VoiceMode::Poly, |ui| {
+            let combo = egui::ComboBox::from_label("Voice stealing")
+                .selected_text(format!("{}", self.voice_stealing))
+                .show_ui(ui, |ui| {
+                    for stealing in VoiceStealing::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.voice_stealing,
+                                stealing,
+                                format!("{stealing}"),
+                            )
+                            .clicked()
+                        {
+                            self.synth.send_event(Event::SetVoiceStealing(stealing));
+                        }
+                    }
+                });
+            self.midi_learn.menu(combo.response, ParamId::VoiceStealing);
+        });
+    }
+
+    fn show_oscillator_bank(&mut self, ui: &mut egui::Ui) {
+        let defaults = Patch::default();
+        let wavetables = self.wavetables();
+        let name = wavetables
+            .iter()
+            .find(|(kind, _)| *kind == self.current_wavetable)
+            .map(|(_, name)| name.clone())
+            .unwrap_or_else(|| format!("{}", self.current_wavetable));
+        let combo = egui::ComboBox::from_label("Waveform")
+            .selected_text(name)
+            .show_ui(ui, |ui| {
+                for (kind, name) in &wavetables {
+                    if ui
+                        .selectable_value(&mut self.current_wavetable, *kind, name)
+                        .clicked()
+                    {
+                        self.synth.send_event(Event::ChangeOscillator(*kind));
+                    }
+                }
+            });
+        self.midi_learn.menu(combo.response, ParamId::Waveform);
+        let combo = egui::ComboBox::from_label("Interpolation")
+            .selected_text(format!("{}", self.interpolation))
+            .show_ui(ui, |ui| {
+                for interpolation in Interpolation::ALL {
+                    if ui
+                        .selectable_value(
+                            &mut self.interpolation,
+                            interpolation,
+                            format!("{interpolation}"),
+                        )
+                        .clicked()
+                    {
+                        self.synth
+                            .send_event(Event::SetInterpolation(interpolation));
+                    }
+                }
+            });
+        self.midi_learn.menu(combo.response, ParamId::Interpolation);
+
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Position", &mut self.position, 0.0..=1.0)
+                            .default_value(defaults.position),
+                    ),
+                    ParamId::Position,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetPosition(self.position));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Pan", &mut self.oscillator_pan, -1.0..=1.0)
+                            .default_value(defaults.oscillator_pan),
+                    ),
+                    ParamId::OscillatorPan,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetOscillatorPan(self.oscillator_pan));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Unison", &mut self.unison_voices, 1..=MAX_UNISON)
+                            .default_value(defaults.unison_voices),
+                    ),
+                    ParamId::UnisonVoices,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetUnisonVoices(self.unison_voices));
+            }
+            ui.add_enabled_ui(self.unison_voices > 1, |ui| {
+                if self
+                    .midi_learn
+                    .menu(
+                        ui.add(
+                            Knob::new("Detune", &mut self.unison_detune, 0.0..=MAX_DETUNE_CENTS)
+                                .default_value(defaults.unison_detune)
+                                .decimals(0)
+                                .suffix(" ct"),
+                        ),
+                        ParamId::UnisonDetune,
+                    )
+                    .changed()
+                {
+                    self.synth
+                        .send_event(Event::SetUnisonDetune(self.unison_detune));
+                }
+                if self
+                    .midi_learn
+                    .menu(
+                        ui.add(
+                            Knob::new("Spread", &mut self.unison_spread, 0.0..=1.0)
+                                .default_value(defaults.unison_spread),
+                        ),
+                        ParamId::UnisonSpread,
+                    )
+                    .changed()
+                {
+                    self.synth
+                        .send_event(Event::SetUnisonSpread(self.unison_spread));
+                }
+            });
+        });
+        ui.add_enabled_ui(self.unison_voices > 1, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Start phase");
+                let mut buttons: Option<egui::Response> = None;
+                for phase in UnisonPhase::ALL {
+                    let button = ui.radio_value(&mut self.unison_phase, phase, format!("{phase}"));
+                    if button.clicked() {
+                        self.synth.send_event(Event::SetUnisonPhase(phase));
+                    }
+                    buttons = Some(match buttons {
+                        Some(buttons) => buttons | button,
+                        None => button,
+                    });
+                }
+                if let Some(buttons) = buttons {
+                    self.midi_learn.menu(buttons, ParamId::UnisonPhase);
+                }
+            });
+        });
+
+        ui.label("Import a single-cycle WAV (or drop it here)");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.wavetable_path);
+            if ui.button("Import").clicked() {
+                let path = PathBuf::from(self.wavetable_path.trim());
+                self.import_wavetable(path);
+            }
+            if self.importing > 0 {
+                ui.spinner();
+            }
+        });
+        if let Some(error) = &self.import_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    // the drive sits where the Model D's external input is, its feedback is the same trick
+    // of patching the output back into the mixer
+    fn show_mixer(&mut self, ui: &mut egui::Ui) {
+        let defaults = Patch::default();
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(Toggle::new("Drive", &mut self.drive.enabled)),
+                    ParamId::DriveEnabled,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetDriveEnabled(self.drive.enabled));
+            }
+            ui.add_enabled_ui(self.drive.enabled, |ui| {
+                if self
+                    .midi_learn
+                    .menu(
+                        ui.add(
+                            Knob::new("Gain", &mut self.drive.drive_db, 0.0..=MAX_DRIVE_DB)
+                                .default_value(defaults.drive.drive_db)
+                                .decimals(1)
+                                .suffix(" dB"),
+                        ),
+                        ParamId::DriveGain,
+                    )
+                    .changed()
+                {
+                    self.synth
+                        .send_event(Event::SetDriveGain(self.drive.drive_db));
+                }
+                if self
+                    .midi_learn
+                    .menu(
+                        ui.add(
+                            Knob::new("Feedback", &mut self.drive.feedback, 0.0..=1.0)
+                                .default_value(defaults.drive.feedback),
+                        ),
+                        ParamId::DriveFeedback,
+                    )
+                    .changed()
+                {
+                    self.synth
+                        .send_event(Event::SetDriveFeedback(self.drive.feedback));
+                }
+            });
+        });
+        ui.add_enabled_ui(self.drive.enabled, |ui| {
+            let combo = egui::ComboBox::from_label("Curve")
+                .selected_text(format!("{}", self.drive.curve))
+                .show_ui(ui, |ui| {
+                    for curve in DriveCurve::ALL {
+                        if ui
+                            .selectable_value(&mut self.drive.curve, curve, format!("{curve}"))
+                            .clicked()
+                        {
+                            self.synth.send_event(Event::SetDriveCurve(curve));
+                        }
+                    }
+                });
+            self.midi_learn.menu(combo.response, ParamId::DriveCurve);
+            let combo = egui::ComboBox::from_label("Oversampling")
+                .selected_text(format!("{}", self.drive.oversampling))
+                .show_ui(ui, |ui| {
+                    for oversampling in Oversampling::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.drive.oversampling,
+                                oversampling,
+                                format!("{oversampling}"),
+                            )
+                            .clicked()
+                        {
+                            self.synth
+                                .send_event(Event::SetDriveOversampling(oversampling));
+                        }
+                    }
+                });
+            self.midi_learn
+                .menu(combo.response, ParamId::DriveOversampling);
+        });
+    }
+
+    fn show_modifiers(&mut self, ui: &mut egui::Ui) {
+        let defaults = Patch::default().envelope;
+        ui.label("Loudness Contour");
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Attack", &mut self.envelope.attack_ms, 5..=10000)
+                            .default_value(defaults.attack_ms)
+                            .logarithmic(true)
+                            .suffix(" ms"),
+                    ),
+                    ParamId::Attack,
                 )
-                .dragged()
+                .changed()
             {
                 self.synth
                     .send_event(Event::SetAttackMs(self.envelope.attack_ms));
             }
-            if ui
-                .add(
-                    egui::Slider::new(&mut self.envelope.decay_ms, 5..=10000)
-                        .logarithmic(true)
-                        .text("Decay (ms)"),
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Decay", &mut self.envelope.decay_ms, 5..=10000)
+                            .default_value(defaults.decay_ms)
+                            .logarithmic(true)
+                            .suffix(" ms"),
+                    ),
+                    ParamId::Decay,
                 )
-                .dragged()
+                .changed()
             {
                 self.synth
                     .send_event(Event::SetDecayMs(self.envelope.decay_ms));
             }
-            if ui
-                .add(egui::Slider::new(&mut self.envelope.sustain, 0.0..=1.0).text("Sustain"))
-                .dragged()
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Sustain", &mut self.envelope.sustain, 0.0..=1.0)
+                            .default_value(defaults.sustain),
+                    ),
+                    ParamId::Sustain,
+                )
+                .changed()
             {
                 self.synth
                     .send_event(Event::SetSustain(self.envelope.sustain));
             }
-            if ui
-                .add(
-                    egui::Slider::new(&mut self.envelope.release_ms, 5..=10000)
-                        .logarithmic(true)
-                        .text("Release (ms)"),
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Release", &mut self.envelope.release_ms, 5..=10000)
+                            .default_value(defaults.release_ms)
+                            .logarithmic(true)
+                            .suffix(" ms"),
+                    ),
+                    ParamId::Release,
                 )
-                .dragged()
+                .changed()
             {
                 self.synth
                     .send_event(Event::SetReleaseMs(self.envelope.release_ms));
             }
+        });
+        // as wide as the knobs above it
+        let width = ui.min_rect().width();
+        ui.scope(|ui| {
+            ui.set_max_width(width);
+            let voices = self.synth.voice_monitor().load();
+            envelope_view::show(ui, &self.envelope, &voices);
+        });
+    }
+
+    fn show_output(&mut self, ui: &mut egui::Ui) {
+        let defaults = Patch::default();
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Volume", &mut self.master_volume, 0.0..=1.0)
+                            .default_value(defaults.master),
+                    ),
+                    ParamId::Master,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetMaster(self.master_volume));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(Knob::new("Pan", &mut self.pan, -1.0..=1.0).default_value(defaults.pan)),
+                    ParamId::Pan,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetPan(self.pan));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Width", &mut self.width, 0.0..=MAX_WIDTH)
+                            .default_value(defaults.width),
+                    ),
+                    ParamId::Width,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetWidth(self.width));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Ceiling", &mut self.ceiling_db, MIN_CEILING_DB..=0.0)
+                            .default_value(defaults.ceiling_db)
+                            .decimals(1)
+                            .suffix(" dB"),
+                    ),
+                    ParamId::Ceiling,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetCeiling(self.ceiling_db));
+            }
+        });
+        ui.horizontal(|ui| {
+            let peak_db = 20.0 * self.output_peak.max(1e-5).log10();
+            ui.label(format!("Peak {peak_db:.1} dB"));
+            let clipping = self
+                .clipped_at
+                .is_some_and(|clipped_at| clipped_at.elapsed() < CLIP_HOLD);
+            if clipping {
+                ui.colored_label(egui::Color32::RED, "CLIP");
+            } else {
+                ui.weak("CLIP");
+            }
+        });
+    }
 
-            let events = ui.ctx().input(|i| i.events.clone());
-            'event_loop: for event in &events {
-                match event {
-                    egui::Event::Key {
-                        key: Key::Escape,
-                        pressed: false, // KeyUp
-                        ..
-                    } => ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close),
-                    egui::Event::Key {
-                        key: Key::Z,
-                        pressed: false, // KeyUp
-                        ..
-                    } => self.synth.send_event(Event::OctaveDown),
-                    egui::Event::Key {
-                        key: Key::X,
-                        pressed: false, // KeyUp
-                        ..
-                    } => self.synth.send_event(Event::OctaveUp),
-                    egui::Event::Key { key, pressed, .. } => {
-                        let note = keymap(key, self.root_note);
-                        if note.is_none() {
-                            continue 'event_loop;
+    fn show_effects(&mut self, ui: &mut egui::Ui) {
+        let defaults = Patch::default();
+        if self
+            .midi_learn
+            .menu(
+                ui.add(
+                    egui::DragValue::new(&mut self.tempo)
+                        .range(MIN_TEMPO_BPM..=MAX_TEMPO_BPM)
+                        .suffix(" BPM"),
+                ),
+                ParamId::Tempo,
+            )
+            .changed()
+        {
+            self.synth.send_event(Event::SetTempo(self.tempo));
+        }
+
+        ui.separator();
+        ui.label("Chorus");
+        ui.horizontal(|ui| {
+            let mut buttons: Option<egui::Response> = None;
+            for mode in ChorusMode::ALL {
+                let button = ui.radio_value(&mut self.chorus.mode, mode, format!("{mode}"));
+                if button.clicked() {
+                    self.synth.send_event(Event::SetChorusMode(mode));
+                }
+                buttons = Some(match buttons {
+                    Some(buttons) => buttons | button,
+                    None => button,
+                });
+            }
+            if let Some(buttons) = buttons {
+                self.midi_learn.menu(buttons, ParamId::ChorusMode);
+            }
+        });
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Rate", &mut self.chorus.rate_hz, 0.05..=MAX_CHORUS_RATE_HZ)
+                            .default_value(defaults.chorus.rate_hz)
+                            .logarithmic(true)
+                            .suffix(" Hz"),
+                    ),
+                    ParamId::ChorusRate,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetChorusRate(self.chorus.rate_hz));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Depth", &mut self.chorus.depth, 0.0..=1.0)
+                            .default_value(defaults.chorus.depth),
+                    ),
+                    ParamId::ChorusDepth,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetChorusDepth(self.chorus.depth));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Voices", &mut self.chorus.voices, 1..=MAX_CHORUS_VOICES)
+                            .default_value(defaults.chorus.voices),
+                    ),
+                    ParamId::ChorusVoices,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetChorusVoices(self.chorus.voices));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Mix", &mut self.chorus.mix, 0.0..=1.0)
+                            .default_value(defaults.chorus.mix),
+                    ),
+                    ParamId::ChorusMix,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetChorusMix(self.chorus.mix));
+            }
+        });
+
+        ui.separator();
+        ui.label("Delay");
+        ui.horizontal(|ui| {
+            let synced = matches!(self.delay.time, DelayTime::Sync(_));
+            if ui.radio(!synced, "ms").clicked() && synced {
+                self.delay.time = DelayTime::Ms(self.delay.time.ms(self.tempo).min(MAX_DELAY_MS));
+                self.synth.send_event(Event::SetDelayTime(self.delay.time));
+            }
+            if ui.radio(synced, "Sync").clicked() && !synced {
+                self.delay.time = DelayTime::Sync(NoteDivision::Quarter);
+                self.synth.send_event(Event::SetDelayTime(self.delay.time));
+            }
+            if let DelayTime::Sync(division) = &mut self.delay.time {
+                let mut changed = false;
+                let combo = egui::ComboBox::from_id_salt("DelayDivision")
+                    .selected_text(format!("{division}"))
+                    .show_ui(ui, |ui| {
+                        for option in NoteDivision::ALL {
+                            changed |= ui
+                                .selectable_value(division, option, format!("{option}"))
+                                .clicked();
                         }
-                        let note = note.unwrap();
-                        match pressed {
-                            // KeyDown
-                            true => {
-                                if !self.pressed_keys.contains(key) {
-                                    self.synth.send_event(Event::NoteOn(note));
-                                    self.pressed_keys.insert(*key);
-                                }
+                    });
+                self.midi_learn.menu(combo.response, ParamId::DelayTime);
+                if changed {
+                    self.synth.send_event(Event::SetDelayTime(self.delay.time));
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            if let DelayTime::Ms(ms) = &mut self.delay.time {
+                if self
+                    .midi_learn
+                    .menu(
+                        ui.add(
+                            Knob::new("Time", ms, 1.0..=MAX_DELAY_MS)
+                                .logarithmic(true)
+                                .decimals(0)
+                                .suffix(" ms"),
+                        ),
+                        ParamId::DelayTime,
+                    )
+                    .changed()
+                {
+                    self.synth.send_event(Event::SetDelayTime(self.delay.time));
+                }
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Feedback", &mut self.delay.feedback, 0.0..=MAX_FEEDBACK)
+                            .default_value(defaults.delay.feedback),
+                    ),
+                    ParamId::DelayFeedback,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetDelayFeedback(self.delay.feedback));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Tone", &mut self.delay.tone_hz, 200.0..=20000.0)
+                            .default_value(defaults.delay.tone_hz)
+                            .logarithmic(true)
+                            .decimals(0)
+                            .suffix(" Hz"),
+                    ),
+                    ParamId::DelayTone,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetDelayTone(self.delay.tone_hz));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(Toggle::new("Ping-pong", &mut self.delay.ping_pong)),
+                    ParamId::DelayPingPong,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetDelayPingPong(self.delay.ping_pong));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Mix", &mut self.delay.mix, 0.0..=1.0)
+                            .default_value(defaults.delay.mix),
+                    ),
+                    ParamId::DelayMix,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetDelayMix(self.delay.mix));
+            }
+        });
+
+        ui.separator();
+        ui.label("Reverb");
+        ui.horizontal(|ui| {
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Size", &mut self.reverb.size, 0.1..=1.0)
+                            .default_value(defaults.reverb.size),
+                    ),
+                    ParamId::ReverbSize,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetReverbSize(self.reverb.size));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Decay", &mut self.reverb.decay_s, 0.1..=MAX_DECAY_S)
+                            .default_value(defaults.reverb.decay_s)
+                            .logarithmic(true)
+                            .decimals(1)
+                            .suffix(" s"),
+                    ),
+                    ParamId::ReverbDecay,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetReverbDecay(self.reverb.decay_s));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Damping", &mut self.reverb.damping, 0.0..=1.0)
+                            .default_value(defaults.reverb.damping),
+                    ),
+                    ParamId::ReverbDamping,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetReverbDamping(self.reverb.damping));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new(
+                            "Pre-delay",
+                            &mut self.reverb.pre_delay_ms,
+                            0.0..=MAX_PRE_DELAY_MS,
+                        )
+                        .default_value(defaults.reverb.pre_delay_ms)
+                        .decimals(0)
+                        .suffix(" ms"),
+                    ),
+                    ParamId::ReverbPreDelay,
+                )
+                .changed()
+            {
+                self.synth
+                    .send_event(Event::SetReverbPreDelay(self.reverb.pre_delay_ms));
+            }
+            if self
+                .midi_learn
+                .menu(
+                    ui.add(
+                        Knob::new("Mix", &mut self.reverb.mix, 0.0..=1.0)
+                            .default_value(defaults.reverb.mix),
+                    ),
+                    ParamId::ReverbMix,
+                )
+                .changed()
+            {
+                self.synth.send_event(Event::SetReverbMix(self.reverb.mix));
+            }
+        });
+    }
+
+    fn show_tuning(&mut self, ui: &mut egui::Ui) {
+        if self.tuning.scale.description.is_empty() {
+            ui.label(format!("Scale: {} notes", self.tuning.scale.len()));
+        } else {
+            ui.label(format!("Scale: {}", self.tuning.scale.description));
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.tuning.scale_path);
+            if ui.button("Load .scl").clicked() {
+                self.load_scale();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.tuning.mapping_path);
+            if ui.button("Load .kbm").clicked() {
+                self.load_mapping();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Reference");
+            let note = ui.add(
+                egui::DragValue::new(&mut self.tuning.mapping.reference_note)
+                    .range(0..=127)
+                    .prefix("note "),
+            );
+            let frequency = ui.add(
+                egui::DragValue::new(&mut self.tuning.mapping.reference_frequency)
+                    .range(1.0..=20000.0)
+                    .speed(0.1)
+                    .suffix(" Hz"),
+            );
+            if note.changed() || frequency.changed() {
+                self.synth.send_event(Event::SetTuningReference(
+                    MidiNote {
+                        note: self.tuning.mapping.reference_note,
+                    },
+                    self.tuning.mapping.reference_frequency as f32,
+                ));
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.tuning.sysex_path);
+            if ui.button("Load MTS .syx").clicked() {
+                self.load_sysex();
+            }
+        });
+        if !self.tuning.retuning.lock().unwrap().is_empty() {
+            ui.label("Some notes are retuned through MTS");
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Reset to 12-TET").clicked() {
+                self.reset_tuning();
+            }
+            if ui.button("Reset transpose").clicked() {
+                self.set_transpose(Transpose::default());
+            }
+        });
+        if let Some(error) = &self.tuning.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    fn handle_keys(&mut self, ctx: &egui::Context, typing: bool) {
+        let events = ctx.input(|i| i.events.clone());
+        if typing {
+            // the keys go to the text field, notes held from before stop
+            for (_, note) in self.pressed_keys.drain() {
+                self.synth.send_event(Event::NoteOff(note));
+            }
+            for event in &events {
+                if let egui::Event::Key {
+                    key, pressed: true, ..
+                } = event
+                {
+                    self.ignored_keys.insert(*key);
+                }
+            }
+            return;
+        }
+
+        'event_loop: for event in &events {
+            // a release that never came, with the window out of focus, is forgotten on the
+            // next press
+            if let egui::Event::Key {
+                key,
+                pressed: true,
+                repeat: false,
+                ..
+            } = event
+            {
+                self.ignored_keys.remove(key);
+            }
+            match event {
+                egui::Event::Key {
+                    key,
+                    pressed: false, // KeyUp
+                    ..
+                } if self.ignored_keys.remove(key) => {}
+                // repeats of a key held since it was typed
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } if self.ignored_keys.contains(key) => {}
+                egui::Event::Key {
+                    key: Key::Escape,
+                    pressed: false, // KeyUp
+                    ..
+                } if self.layout.learning.is_some() => self.layout.learning = None,
+                egui::Event::Key {
+                    key: Key::Escape,
+                    pressed: false, // KeyUp
+                    ..
+                } if self.midi_learn.learning.is_some() => self.midi_learn.learning = None,
+                egui::Event::Key {
+                    key: Key::Escape,
+                    pressed: false, // KeyUp
+                    ..
+                } => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+                // keys held with command or ctrl are shortcuts, not notes, but a note that
+                // was already playing still stops on its release
+                egui::Event::Key { key, modifiers, .. }
+                    if (modifiers.command || modifiers.ctrl)
+                        && !self.pressed_keys.contains_key(key) => {}
+                egui::Event::Key {
+                    key, pressed: true, ..
+                } if *key != Key::Escape && self.layout.learning.is_some() => {
+                    let offset = self.layout.learning.take().unwrap_or_default();
+                    let key = *key;
+                    self.layout.customize(|keymap| {
+                        keymap.bind(key, offset);
+                    });
+                }
+                egui::Event::Key {
+                    key,
+                    pressed: false, // KeyUp
+                    ..
+                } if *key == self.layout.keymap.octave_down => {
+                    self.keyboard_octave = self.keyboard_octave.saturating_sub(1)
+                }
+                egui::Event::Key {
+                    key,
+                    pressed: false, // KeyUp
+                    ..
+                } if *key == self.layout.keymap.octave_up => {
+                    self.keyboard_octave = (self.keyboard_octave + 1).min(MAX_KEYBOARD_OCTAVE)
+                }
+                egui::Event::Key { key, pressed, .. } => {
+                    let note = self.layout.keymap.note(*key, self.root_note());
+                    if note.is_none() {
+                        continue 'event_loop;
+                    }
+                    let note = note.unwrap();
+                    match pressed {
+                        // KeyDown
+                        true => {
+                            if !self.pressed_keys.contains_key(key) {
+                                self.synth.send_event(Event::NoteOn(note, DEFAULT_VELOCITY));
+                                self.pressed_keys.insert(*key, note);
                             }
-                            // KeyUp
-                            false => {
-                                if self.pressed_keys.contains(key) {
-                                    self.synth.send_event(Event::NoteOff(note));
-                                    self.pressed_keys.remove(key);
-                                }
+                        }
+                        // KeyUp
+                        false => {
+                            if let Some(note) = self.pressed_keys.remove(key) {
+                                self.synth.send_event(Event::NoteOff(note));
                             }
                         }
                     }
-                    _ => {}
                 }
+                _ => {}
             }
-        });
-    }
-}
-fn keymap(keycode: &Key, root: MidiNote) -> Option<MidiNote> {
-    match keycode {
-        // second row is white keys
-        Key::A => Some(root),
-        Key::S => Some(root.offset_up(2)),
-        Key::D => Some(root.offset_up(4)),
-        Key::F => Some(root.offset_up(5)),
-        Key::G => Some(root.offset_up(7)),
-        Key::H => Some(root.offset_up(9)),
-        Key::J => Some(root.offset_up(11)),
-        Key::K => Some(root.offset_up(12)),
-        Key::L => Some(root.offset_up(14)),
-        Key::Semicolon => Some(root.offset_up(16)),
-        Key::Quote => Some(root.offset_up(17)),
-        // first row is black keys
-        Key::W => Some(root.offset_up(1)),
-        Key::E => Some(root.offset_up(3)),
-        Key::T => Some(root.offset_up(6)),
-        Key::Y => Some(root.offset_up(8)),
-        Key::U => Some(root.offset_up(10)),
-        Key::I => Some(root.offset_up(13)),
-        Key::O => Some(root.offset_up(15)),
-        _ => None,
+        }
     }
 }
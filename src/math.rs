@@ -1,3 +1,270 @@
+use std::f32::consts::{PI, TAU};
+use std::ops::{Add, Mul, Sub};
+
 pub fn lerp(x: f32, a: f32, b: f32) -> f32 {
     x * b + (1.0 - x) * a
 }
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_angle(angle: f32) -> Self {
+        Self::new(angle.cos(), angle.sin())
+    }
+
+    pub fn conj(&self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn scale(&self, x: f32) -> Self {
+        Self::new(x * self.re, x * self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+// in-place radix-2, unnormalized
+// panics if the length is not a power of two
+pub fn fft(data: &mut [Complex]) {
+    fft_impl(data, false);
+}
+
+// in-place radix-2, normalized by 1/n so that ifft(fft(x)) == x
+pub fn ifft(data: &mut [Complex]) {
+    fft_impl(data, true);
+    let n = data.len() as f32;
+    for x in data.iter_mut() {
+        *x = x.scale(1.0 / n);
+    }
+}
+
+fn fft_impl(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "fft size must be a power of two");
+
+    // bit reversal permutation
+    let mut j: usize = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign: f32 = if inverse { 1.0 } else { -1.0 };
+    let mut len: usize = 2;
+    while len <= n {
+        let step = Complex::from_angle(sign * TAU / len as f32);
+        for start in (0..n).step_by(len) {
+            let mut w = Complex::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let even = data[start + k];
+                let odd = data[start + k + len / 2] * w;
+                data[start + k] = even + odd;
+                data[start + k + len / 2] = even - odd;
+                w = w * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// forward transform of a real signal of any length
+// other lengths than powers of two go through Bluestein's algorithm, which turns the
+// transform into a convolution done with power of two ffts
+pub fn dft(input: &[f32]) -> Vec<Complex> {
+    let n = input.len();
+    let mut data: Vec<Complex> = input.iter().map(|x| Complex::new(*x, 0.0)).collect();
+    if n == 0 || n.is_power_of_two() {
+        if n > 0 {
+            fft(&mut data);
+        }
+        return data;
+    }
+
+    // e^(iπk²/n), k² is reduced modulo 2n first so that the angle keeps its precision
+    let chirp: Vec<Complex> = (0..n as u64)
+        .map(|k| Complex::from_angle(PI * ((k * k) % (2 * n as u64)) as f32 / n as f32))
+        .collect();
+    let size = (2 * n - 1).next_power_of_two();
+    let mut a: Vec<Complex> = vec![Complex::default(); size];
+    let mut b: Vec<Complex> = vec![Complex::default(); size];
+    for k in 0..n {
+        a[k] = data[k] * chirp[k].conj();
+        b[k] = chirp[k];
+        if k > 0 {
+            b[size - k] = chirp[k];
+        }
+    }
+    fft(&mut a);
+    fft(&mut b);
+    for (x, y) in a.iter_mut().zip(&b) {
+        *x = *x * *y;
+    }
+    ifft(&mut a);
+    for (k, x) in data.iter_mut().enumerate() {
+        *x = a[k] * chirp[k].conj();
+    }
+
+    data
+}
+
+// 4-point, 3rd-order Hermite (Catmull-Rom) between `y1` and `y2`
+pub fn hermite(x: f32, y0: f32, y1: f32, y2: f32, y3: f32) -> f32 {
+    let c1 = 0.5 * (y2 - y0);
+    let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+    let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+
+    ((c3 * x + c2) * x + c1) * x + y1
+}
+
+// 4-point, 3rd-order Lagrange between `y1` and `y2`
+pub fn lagrange(x: f32, y0: f32, y1: f32, y2: f32, y3: f32) -> f32 {
+    let xm1 = x + 1.0;
+    let xm2 = x - 1.0;
+    let xm3 = x - 2.0;
+
+    -y0 * x * xm2 * xm3 / 6.0 + y1 * xm1 * xm2 * xm3 / 2.0 - y2 * xm1 * x * xm3 / 2.0
+        + y3 * xm1 * x * xm2 / 6.0
+}
+
+pub fn sinc(x: f32) -> f32 {
+    if x.abs() < f32::EPSILON {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// evaluated on `[-1, 1]`, zero outside
+pub fn blackman(x: f32) -> f32 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    let t = 0.5 * (x + 1.0);
+
+    0.42 - 0.5 * (TAU * t).cos() + 0.08 * (2.0 * TAU * t).cos()
+}
+
+// total harmonic distortion plus noise of a recorded sine, in dB relative to the sine
+// `signal` must hold exactly `cycles` periods, then the sine is found by correlation and
+// everything else is distortion or noise
+// in f64 throughout, an f32 fft has a noise floor of its own around -95 dB
+pub fn thd_n_db(signal: &[f32], cycles: usize) -> f32 {
+    let n = signal.len();
+    let mean: f64 = signal.iter().map(|x| *x as f64).sum::<f64>() / n as f64;
+    let (mut re, mut im, mut total) = (0.0, 0.0, 0.0);
+    for (i, x) in signal.iter().enumerate() {
+        let x = *x as f64 - mean;
+        let angle = std::f64::consts::TAU * ((cycles * i) % n) as f64 / n as f64;
+        re += x * angle.cos();
+        im += x * angle.sin();
+        total += x * x;
+    }
+    // mean powers
+    let tone = 2.0 * (re * re + im * im) / (n * n) as f64;
+    let total = total / n as f64;
+
+    (10.0 * ((total - tone).max(f64::MIN_POSITIVE) / tone).log10()) as f32
+}
+
+// xorshift32, good enough for start phases and modulation jitter, not for anything else
+#[derive(Copy, Clone, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive_dft(input: &[f32]) -> Vec<Complex> {
+        let n = input.len();
+        (0..n)
+            .map(|k| {
+                input
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| {
+                        let angle = -std::f64::consts::TAU * ((k * i) % n) as f64 / n as f64;
+                        Complex::from_angle(angle as f32).scale(*x)
+                    })
+                    .fold(Complex::default(), |acc, x| acc + x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dft_of_any_length_matches_the_definition() {
+        let mut rng = Rng::new(7);
+        for n in [1, 2, 3, 5, 12, 100, 600, 1000, 1024] {
+            let input: Vec<f32> = (0..n).map(|_| 2.0 * rng.next_f32() - 1.0).collect();
+            let expected = naive_dft(&input);
+            for (k, (x, y)) in dft(&input).iter().zip(&expected).enumerate() {
+                let error = (*x - *y).norm();
+                assert!(
+                    error < 1e-3 * (n as f32).sqrt(),
+                    "n = {n}, bin {k}: {error}"
+                );
+            }
+        }
+    }
+}
@@ -1,3 +1,6 @@
+// the velocity used when there is nothing to derive one from
+pub const DEFAULT_VELOCITY: u8 = 100;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct MidiNote {
     pub note: u8,
@@ -37,3 +40,13 @@ impl MidiNote {
         Self::c0().octave_up(octave)
     }
 }
+
+impl std::fmt::Display for MidiNote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+        ];
+        let octave = self.note as i16 / 12 - 1;
+        write!(f, "{}{}", NAMES[self.note as usize % 12], octave)
+    }
+}
@@ -0,0 +1,386 @@
+use std::path::{Path, PathBuf};
+
+use crate::event::Event;
+use crate::midi::MidiNote;
+use crate::synth::{MtsMessage, MtsTable};
+
+// Standard MIDI Files, see the MIDI 1.0 spec "Standard MIDI Files"
+// only format 0 and 1, the notes and MTS messages are kept and the rest is skipped
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const PROGRAM_CHANGE: u8 = 0xC0;
+const CHANNEL_PRESSURE: u8 = 0xD0;
+const SYSEX: u8 = 0xF0;
+const SYSEX_ESCAPE: u8 = 0xF7;
+const META: u8 = 0xFF;
+const SET_TEMPO: u8 = 0x51;
+
+// 120 BPM, until the file sets another tempo
+const DEFAULT_TEMPO_US: u32 = 500_000;
+
+#[allow(clippy::large_enum_variant)]
+enum TrackEvent {
+    Tempo(u32),
+    Synth(Event),
+}
+
+// the events of every track in time order, with their time in seconds
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<(f64, Event)>, MidiFileError> {
+    let path = path.as_ref();
+    std::fs::read(path)
+        .map_err(MidiFileError::Io)
+        .and_then(|bytes| parse(&bytes))
+        .map_err(|err| MidiFileError::InFile(path.to_path_buf(), Box::new(err)))
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Vec<(f64, Event)>, MidiFileError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let (id, mut header) = reader.chunk()?;
+    if id != *b"MThd" {
+        return Err(MidiFileError::NotMidi);
+    }
+    let format = header.u16()?;
+    let _track_count = header.u16()?;
+    let division = header.u16()?;
+    if format > 1 {
+        return Err(MidiFileError::UnsupportedFormat(format));
+    }
+    if division & 0x8000 != 0 && !matches!(smpte_frames(division), 24 | 25 | 29 | 30) {
+        return Err(MidiFileError::BadDivision(division));
+    }
+
+    // tracks are read in order, so a tempo change in the first track comes before the
+    // notes at the same tick in the others
+    let mut events: Vec<(u64, TrackEvent)> = Vec::new();
+    while !reader.is_empty() {
+        let (id, track) = reader.chunk()?;
+        // unknown chunks are allowed and skipped
+        if id == *b"MTrk" {
+            events.extend(parse_track(track)?);
+        }
+    }
+    events.sort_by_key(|(tick, _)| *tick);
+
+    let mut tempo_us = DEFAULT_TEMPO_US;
+    let mut seconds = 0.0;
+    let mut last_tick = 0;
+    let mut timed = Vec::new();
+    for (tick, event) in events {
+        seconds += (tick - last_tick) as f64 * seconds_per_tick(division, tempo_us);
+        last_tick = tick;
+        match event {
+            TrackEvent::Tempo(tempo) => tempo_us = tempo,
+            TrackEvent::Synth(event) => timed.push((seconds, event)),
+        }
+    }
+
+    Ok(timed)
+}
+
+fn seconds_per_tick(division: u16, tempo_us: u32) -> f64 {
+    if division & 0x8000 == 0 {
+        // ticks per quarter note
+        tempo_us as f64 / 1e6 / division.max(1) as f64
+    } else {
+        // SMPTE frames per second and ticks per frame, 29 is 30 drop-frame
+        let frames = match smpte_frames(division) {
+            29 => 29.97,
+            frames => frames as f64,
+        };
+        let ticks = (division & 0xFF) as f64;
+        1.0 / (frames * ticks.max(1.0))
+    }
+}
+
+// the high byte is the frame rate as a negative number, only -24, -25, -29 and -30 are valid
+fn smpte_frames(division: u16) -> i16 {
+    -(((division >> 8) as u8 as i8) as i16)
+}
+
+fn parse_track(mut track: Reader) -> Result<Vec<(u64, TrackEvent)>, MidiFileError> {
+    let mut events = Vec::new();
+    let mut tick: u64 = 0;
+    let mut running_status: Option<u8> = None;
+    while !track.is_empty() {
+        tick += track.variable_length()? as u64;
+        let mut status = track.u8()?;
+        match status {
+            META => {
+                running_status = None;
+                let kind = track.u8()?;
+                let length = track.variable_length()? as usize;
+                let data = track.take(length)?;
+                if kind == SET_TEMPO && data.len() == 3 {
+                    let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                    events.push((tick, TrackEvent::Tempo(tempo)));
+                }
+            }
+            SYSEX => {
+                running_status = None;
+                let length = track.variable_length()? as usize;
+                let mut sysex = vec![SYSEX];
+                sysex.extend_from_slice(track.take(length)?);
+                if let Ok(message) = MtsMessage::parse(&sysex) {
+                    let changes = MtsTable::changes(&message);
+                    events.push((tick, TrackEvent::Synth(Event::Retune(changes))));
+                }
+            }
+            SYSEX_ESCAPE => {
+                running_status = None;
+                let length = track.variable_length()? as usize;
+                track.take(length)?;
+            }
+            _ => {
+                // a data byte, the status is the previous one
+                if status < 0x80 {
+                    status = running_status.ok_or(MidiFileError::Truncated)?;
+                    track.position -= 1;
+                }
+                running_status = Some(status);
+                let data = match status & 0xF0 {
+                    PROGRAM_CHANGE | CHANNEL_PRESSURE => track.take(1)?,
+                    _ => track.take(2)?,
+                };
+                let note = MidiNote {
+                    note: data[0] & 0x7F,
+                };
+                let velocity = data.get(1).map(|velocity| velocity & 0x7F);
+                let event = match (status & 0xF0, velocity) {
+                    (NOTE_ON, Some(0)) | (NOTE_OFF, _) => Some(Event::NoteOff(note)),
+                    (NOTE_ON, Some(velocity)) => Some(Event::NoteOn(note, velocity)),
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    events.push((tick, TrackEvent::Synth(event)));
+                }
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MidiFileError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or(MidiFileError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MidiFileError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MidiFileError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, MidiFileError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // seven bits per byte, the top bit is set on all but the last, four bytes at most
+    fn variable_length(&mut self) -> Result<u32, MidiFileError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MidiFileError::Truncated)
+    }
+
+    fn chunk(&mut self) -> Result<([u8; 4], Reader<'a>), MidiFileError> {
+        let id = self.take(4)?;
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        Ok(([id[0], id[1], id[2], id[3]], Reader { bytes, position: 0 }))
+    }
+}
+
+#[derive(Debug)]
+pub enum MidiFileError {
+    Io(std::io::Error),
+    NotMidi,
+    Truncated,
+    UnsupportedFormat(u16),
+    BadDivision(u16),
+    InFile(PathBuf, Box<MidiFileError>),
+}
+
+impl std::fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiFileError::Io(err) => write!(f, "{}", err),
+            MidiFileError::NotMidi => write!(f, "not a Standard MIDI File"),
+            MidiFileError::Truncated => write!(f, "truncated MIDI file"),
+            MidiFileError::UnsupportedFormat(format) => {
+                write!(f, "unsupported MIDI file format {}", format)
+            }
+            MidiFileError::BadDivision(division) => {
+                write!(f, "invalid time division {:#06x}", division)
+            }
+            MidiFileError::InFile(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for MidiFileError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn smf(format: u16, division: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend_from_slice(&6_u32.to_be_bytes());
+        bytes.extend_from_slice(&format.to_be_bytes());
+        bytes.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&division.to_be_bytes());
+        for track in tracks {
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(track);
+        }
+        bytes
+    }
+
+    // the time, the note and the velocity, `None` for a note off
+    fn notes(events: &[(f64, Event)]) -> Vec<(f64, u8, Option<u8>)> {
+        events
+            .iter()
+            .map(|(time, event)| match event {
+                Event::NoteOn(note, velocity) => (*time, note.note, Some(*velocity)),
+                Event::NoteOff(note) => (*time, note.note, None),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect()
+    }
+
+    const END_OF_TRACK: [u8; 4] = [0x00, META, 0x2F, 0x00];
+
+    #[test]
+    fn running_status_and_note_on_without_velocity() {
+        // 96 ticks per quarter note at the default 120 BPM, a tick is 1/192 s
+        let track = [
+            &[0x00, 0x90, 60, 100][..],
+            // running status: two more note ons, then C4 released by a note on with velocity 0
+            &[0x60, 64, 90],
+            &[0x00, 67, 80],
+            &[0x60, 60, 0],
+            // a controller in between resets nothing, it just changes the running status
+            &[0x00, 0xB0, 7, 100],
+            &[0x00, 0x80, 64, 64],
+            &[0x00, 0x90, 67, 0],
+            &END_OF_TRACK,
+        ]
+        .concat();
+        let events = parse(&smf(0, 96, &[&track])).unwrap();
+        assert_eq!(
+            notes(&events),
+            [
+                (0.0, 60, Some(100)),
+                (0.5, 64, Some(90)),
+                (0.5, 67, Some(80)),
+                (1.0, 60, None),
+                (1.0, 64, None),
+                (1.0, 67, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn tempo_changes_apply_to_every_track() {
+        // the tempo map in the first track: 120 BPM, then 240 BPM from the second beat
+        let tempo_map = [
+            &[0x00, META, SET_TEMPO, 3, 0x07, 0xA1, 0x20][..],
+            &[0x60, META, SET_TEMPO, 3, 0x03, 0xD0, 0x90],
+            &END_OF_TRACK,
+        ]
+        .concat();
+        // notes on the first, second and third beat in the second
+        let notes_track = [
+            &[0x00, 0x90, 60, 100][..],
+            &[0x60, 0x90, 62, 100],
+            &[0x60, 0x90, 64, 100],
+            &END_OF_TRACK,
+        ]
+        .concat();
+        let events = parse(&smf(1, 96, &[&tempo_map, &notes_track])).unwrap();
+        assert_eq!(
+            notes(&events),
+            [
+                (0.0, 60, Some(100)),
+                (0.5, 62, Some(100)),
+                (0.75, 64, Some(100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn smpte_division() {
+        // 25 frames per second and 40 ticks per frame, a tick is a millisecond
+        let track = [
+            &[0x00, 0x90, 60, 100][..],
+            &[0x83, 0x74, 0x80, 60, 0],
+            &END_OF_TRACK,
+        ]
+        .concat();
+        let events = parse(&smf(0, 0xE728, &[&track])).unwrap();
+        assert_eq!(notes(&events), [(0.0, 60, Some(100)), (0.5, 60, None)]);
+
+        for division in [0x8000, 0xE000 | 40, 0xFF28] {
+            assert!(
+                matches!(
+                    parse(&smf(0, division, &[&track])),
+                    Err(MidiFileError::BadDivision(found)) if found == division
+                ),
+                "{division:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let track = [&[0x00, 0x90, 60, 100][..], &END_OF_TRACK].concat();
+        let bytes = smf(0, 96, &[&track]);
+        // the header, the track's id and length, the middle of its data
+        for len in [10, 18, bytes.len() - 2] {
+            assert!(
+                matches!(parse(&bytes[..len]), Err(MidiFileError::Truncated)),
+                "{len} bytes"
+            );
+        }
+        // a note on cut short inside a chunk that says it is complete
+        let cut = smf(0, 96, &[&[0x00, 0x90, 60]]);
+        assert!(matches!(parse(&cut), Err(MidiFileError::Truncated)));
+        // a data byte with no status before it
+        let orphan = smf(0, 96, &[&[0x00, 60, 100]]);
+        assert!(matches!(parse(&orphan), Err(MidiFileError::Truncated)));
+
+        assert!(matches!(
+            parse(b"RIFF\0\0\0\0"),
+            Err(MidiFileError::NotMidi)
+        ));
+        assert!(matches!(
+            parse(&smf(2, 96, &[])),
+            Err(MidiFileError::UnsupportedFormat(2))
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::{mpsc, Arc, Mutex};
+
+use midir::{Ignore, MidiInputConnection};
+
+use crate::event::Event;
+use crate::midi::MidiNote;
+use crate::synth::{MtsMessage, MtsTable};
+
+const CLIENT_NAME: &str = "ModelP";
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xB0;
+const SYSEX: u8 = 0xF0;
+
+// forwards incoming messages to the synth from midir's own thread
+// MTS retunings are also recorded in `retuning` so that they can be saved with the patch
+// control changes go to `controls` instead, the app decides what they are mapped to
+pub struct MidiInput {
+    connection: Option<MidiInputConnection<()>>,
+    retuning: Arc<Mutex<MtsTable>>,
+}
+
+impl MidiInput {
+    pub fn new(retuning: Arc<Mutex<MtsTable>>) -> Self {
+        Self {
+            connection: None,
+            retuning,
+        }
+    }
+
+    pub fn ports() -> Vec<String> {
+        let Ok(input) = midir::MidiInput::new(CLIENT_NAME) else {
+            return Vec::new();
+        };
+        input
+            .ports()
+            .iter()
+            .filter_map(|port| input.port_name(port).ok())
+            .collect()
+    }
+
+    pub fn connect(
+        &mut self,
+        port_name: &str,
+        events: mpsc::Sender<Event>,
+        controls: impl Fn(ControlChange) + Send + 'static,
+    ) -> Result<(), MidiInputError> {
+        self.disconnect();
+
+        let mut input = midir::MidiInput::new(CLIENT_NAME).map_err(MidiInputError::Init)?;
+        // MTS comes in as SysEx, which midir drops by default
+        input.ignore(Ignore::None);
+        let port = input
+            .ports()
+            .into_iter()
+            .find(|port| input.port_name(port).as_deref() == Ok(port_name))
+            .ok_or_else(|| MidiInputError::PortNotFound(port_name.to_string()))?;
+
+        let retuning = self.retuning.clone();
+        let connection = input
+            .connect(
+                &port,
+                CLIENT_NAME,
+                move |_timestamp, message, _| {
+                    if let [status, controller, value] = message {
+                        if status & 0xF0 == CONTROL_CHANGE {
+                            controls(ControlChange {
+                                controller: *controller,
+                                value: *value,
+                            });
+                            return;
+                        }
+                    }
+                    if let Some(event) = parse(message) {
+                        if let Event::Retune(changes) = &event {
+                            retuning.lock().unwrap().merge(changes);
+                        }
+                        let _ = events.send(event);
+                    }
+                },
+                (),
+            )
+            .map_err(|err| MidiInputError::Connect(err.kind()))?;
+        self.connection = Some(connection);
+
+        Ok(())
+    }
+
+    pub fn disconnect(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.close();
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ControlChange {
+    pub controller: u8,
+    pub value: u8,
+}
+
+// channel voice messages are accepted on every channel
+fn parse(message: &[u8]) -> Option<Event> {
+    match message {
+        [SYSEX, ..] => match MtsMessage::parse(message) {
+            Ok(mts) => Some(Event::Retune(MtsTable::changes(&mts))),
+            Err(err) => {
+                eprintln!("ignoring SysEx: {err}");
+                None
+            }
+        },
+        [status, note, 0] if status & 0xF0 == NOTE_ON => {
+            Some(Event::NoteOff(MidiNote { note: *note }))
+        }
+        [status, note, velocity] if status & 0xF0 == NOTE_ON => {
+            Some(Event::NoteOn(MidiNote { note: *note }, *velocity))
+        }
+        [status, note, _] if status & 0xF0 == NOTE_OFF => {
+            Some(Event::NoteOff(MidiNote { note: *note }))
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum MidiInputError {
+    Init(midir::InitError),
+    PortNotFound(String),
+    Connect(midir::ConnectErrorKind),
+}
+
+impl std::fmt::Display for MidiInputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiInputError::Init(err) => write!(f, "{}", err),
+            MidiInputError::PortNotFound(name) => write!(f, "no MIDI input named {:?}", name),
+            MidiInputError::Connect(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+impl std::error::Error for MidiInputError {}
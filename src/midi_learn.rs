@@ -0,0 +1,181 @@
+use egui::{Response, Stroke};
+use serde::{Deserialize, Serialize};
+
+use crate::midi_input::ControlChange;
+use crate::params::ParamId;
+
+// how the controller's travel is spread over the mapped range
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum CcCurve {
+    #[default]
+    Linear,
+    // finer at the bottom of the controller's travel
+    Exponential,
+    // finer at the top
+    Logarithmic,
+}
+
+impl CcCurve {
+    pub const ALL: [CcCurve; 3] = [CcCurve::Linear, CcCurve::Exponential, CcCurve::Logarithmic];
+
+    fn apply(&self, x: f32) -> f32 {
+        match self {
+            CcCurve::Linear => x,
+            CcCurve::Exponential => x * x,
+            CcCurve::Logarithmic => 1.0 - (1.0 - x) * (1.0 - x),
+        }
+    }
+}
+
+impl std::fmt::Display for CcCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            CcCurve::Linear => "Linear",
+            CcCurve::Exponential => "Exponential",
+            CcCurve::Logarithmic => "Logarithmic",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+// a controller driving a parameter, on every channel
+// `min` and `max` are positions across the parameter's range, swapping them turns the
+// controller around
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CcMapping {
+    pub controller: u8,
+    pub param: ParamId,
+    pub min: f32,
+    pub max: f32,
+    pub curve: CcCurve,
+}
+
+impl CcMapping {
+    pub fn new(controller: u8, param: ParamId) -> Self {
+        Self {
+            controller,
+            param,
+            min: 0.0,
+            max: 1.0,
+            curve: CcCurve::default(),
+        }
+    }
+
+    pub fn position(&self, value: u8) -> f32 {
+        let x = self.curve.apply(value.min(127) as f32 / 127.0);
+        self.min + x * (self.max - self.min)
+    }
+}
+
+// the mappings are saved in the config, `changed` says when they need to be
+#[derive(Default)]
+pub struct MidiLearn {
+    pub mappings: Vec<CcMapping>,
+    // the next controller moved is mapped to this parameter
+    pub learning: Option<ParamId>,
+    pub changed: bool,
+}
+
+impl MidiLearn {
+    pub fn new(mappings: Vec<CcMapping>) -> Self {
+        Self {
+            mappings,
+            learning: None,
+            changed: false,
+        }
+    }
+
+    // where the controller puts each parameter it is mapped to
+    pub fn control_change(&mut self, cc: ControlChange) -> Vec<(ParamId, f32)> {
+        if let Some(param) = self.learning.take() {
+            // one controller per parameter, but a controller can drive several
+            self.mappings.retain(|mapping| mapping.param != param);
+            self.mappings.push(CcMapping::new(cc.controller, param));
+            self.changed = true;
+        }
+        self.mappings
+            .iter()
+            .filter(|mapping| mapping.controller == cc.controller)
+            .map(|mapping| (mapping.param, mapping.position(cc.value)))
+            .collect()
+    }
+
+    // right-clicking the control learns a controller for it or edits its mapping
+    pub fn menu(&mut self, response: Response, param: ParamId) -> Response {
+        if self.learning == Some(param) {
+            let color = response.ctx.style().visuals.selection.stroke.color;
+            response.ctx.layer_painter(response.layer_id).rect_stroke(
+                response.rect,
+                2.0,
+                Stroke::new(1.5, color),
+                egui::StrokeKind::Outside,
+            );
+        }
+
+        response.context_menu(|ui| {
+            ui.label(egui::RichText::new(param.to_string()).strong());
+            if self.learning == Some(param) {
+                ui.label("Move a controller…");
+                if ui.button("Cancel").clicked() {
+                    self.learning = None;
+                    ui.close_menu();
+                }
+            } else if ui.button("MIDI learn").clicked() {
+                self.learning = Some(param);
+                ui.close_menu();
+            }
+
+            let mut forget = None;
+            for (index, mapping) in self.mappings.iter_mut().enumerate() {
+                if mapping.param != param {
+                    continue;
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(format!("CC {}", mapping.controller));
+                    if ui.small_button("Forget").clicked() {
+                        forget = Some(index);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    for (bound, prefix) in [(&mut mapping.min, "min "), (&mut mapping.max, "max ")]
+                    {
+                        self.changed |= ui
+                            .add(
+                                egui::DragValue::new(bound)
+                                    .range(0.0..=1.0)
+                                    .speed(0.01)
+                                    .prefix(prefix),
+                            )
+                            .changed();
+                    }
+                });
+                egui::ComboBox::from_id_salt(("CcCurve", index))
+                    .selected_text(format!("{}", mapping.curve))
+                    .show_ui(ui, |ui| {
+                        for curve in CcCurve::ALL {
+                            self.changed |= ui
+                                .selectable_value(&mut mapping.curve, curve, format!("{curve}"))
+                                .clicked();
+                        }
+                    });
+            }
+            if let Some(index) = forget {
+                self.mappings.remove(index);
+                self.changed = true;
+            }
+        });
+
+        let controllers: Vec<String> = self
+            .mappings
+            .iter()
+            .filter(|mapping| mapping.param == param)
+            .map(|mapping| format!("CC {}", mapping.controller))
+            .collect();
+        if controllers.is_empty() {
+            response
+        } else {
+            response.on_hover_text(controllers.join(", "))
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use serde::{Deserialize, Serialize};
+
+use crate::event::Event;
+use crate::patch::Patch;
+use crate::synth::{
+    ChorusMode, DelayTime, DriveCurve, Interpolation, ModSource, Oversampling, Transpose,
+    UnisonPhase, VoiceMode, VoiceStealing, WavetableKind, MAX_CHORUS_RATE_HZ, MAX_CHORUS_VOICES,
+    MAX_DECAY_S, MAX_DELAY_MS, MAX_DETUNE_CENTS, MAX_DRIVE_DB, MAX_FEEDBACK, MAX_PRE_DELAY_MS,
+    MAX_TEMPO_BPM, MAX_UNISON, MAX_VOICES, MAX_WIDTH, MIN_CEILING_DB, MIN_TEMPO_BPM,
+};
+
+// every parameter a controller can be mapped to
+// the serialized names are what the config refers to, so they must not change
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamId {
+    VoiceMode,
+    Polyphony,
+    VoiceStealing,
+    UnisonVoices,
+    UnisonDetune,
+    UnisonSpread,
+    UnisonPhase,
+    Octave,
+    Semitones,
+    MasterTune,
+    Waveform,
+    Interpolation,
+    OscillatorPan,
+    Position,
+    PositionModSource,
+    PositionModAmount,
+    LfoRate,
+    Tempo,
+    DriveEnabled,
+    DriveCurve,
+    DriveGain,
+    DriveFeedback,
+    DriveOversampling,
+    ChorusMode,
+    ChorusRate,
+    ChorusDepth,
+    ChorusVoices,
+    ChorusMix,
+    // in milliseconds, a synced delay switches to a free time when it is moved
+    DelayTime,
+    DelayFeedback,
+    DelayTone,
+    DelayPingPong,
+    DelayMix,
+    ReverbSize,
+    ReverbDecay,
+    ReverbDamping,
+    ReverbPreDelay,
+    ReverbMix,
+    Master,
+    Pan,
+    Width,
+    Ceiling,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ParamRange {
+    Linear(f32, f32),
+    Logarithmic(f32, f32),
+    // whole numbers, both ends included
+    Integer(i32, i32),
+    // an index into the type's `ALL`
+    Choice(usize),
+    // 0 or 1
+    Toggle,
+    // the built-in and imported wavetables, only the app knows how many there are
+    Wavetable,
+}
+
+impl ParamRange {
+    // `position` goes from 0 to 1 across the range
+    pub fn value(&self, position: f32) -> f32 {
+        let position = position.clamp(0.0, 1.0);
+        match *self {
+            ParamRange::Linear(min, max) => min + position * (max - min),
+            ParamRange::Logarithmic(min, max) => min * (max / min).powf(position),
+            ParamRange::Integer(min, max) => (min as f32 + position * (max - min) as f32).round(),
+            ParamRange::Choice(count) => choice(position, count) as f32,
+            ParamRange::Toggle => position.round(),
+            ParamRange::Wavetable => position,
+        }
+    }
+
+    // the nearest value the range allows, in its own units
+    pub fn clamp(&self, value: f32) -> f32 {
+        match *self {
+            ParamRange::Linear(min, max) | ParamRange::Logarithmic(min, max) => {
+                value.clamp(min, max)
+            }
+            ParamRange::Integer(min, max) => value.round().clamp(min as f32, max as f32),
+            ParamRange::Choice(count) => value.round().clamp(0.0, count.saturating_sub(1) as f32),
+            ParamRange::Toggle => value.round().clamp(0.0, 1.0),
+            ParamRange::Wavetable => value.round().max(0.0),
+        }
+    }
+}
+
+// the wavetables `ParamId::Waveform` picks from, the imported ones come after them
+pub const BUILT_IN_WAVEFORMS: [WavetableKind; 2] = [WavetableKind::Triangle, WavetableKind::Square];
+
+// which of `count` options a position picks
+pub fn choice(position: f32, count: usize) -> usize {
+    let last = count.saturating_sub(1);
+    ((position.clamp(0.0, 1.0) * last as f32).round() as usize).min(last)
+}
+
+impl ParamId {
+    pub fn range(&self) -> ParamRange {
+        match self {
+            ParamId::VoiceMode => ParamRange::Choice(VoiceMode::ALL.len()),
+            ParamId::Polyphony => ParamRange::Integer(1, MAX_VOICES as i32),
+            ParamId::VoiceStealing => ParamRange::Choice(VoiceStealing::ALL.len()),
+            ParamId::UnisonVoices => ParamRange::Integer(1, MAX_UNISON as i32),
+            ParamId::UnisonDetune => ParamRange::Linear(0.0, MAX_DETUNE_CENTS),
+            ParamId::UnisonSpread => ParamRange::Linear(0.0, 1.0),
+            ParamId::UnisonPhase => ParamRange::Choice(UnisonPhase::ALL.len()),
+            ParamId::Octave => ParamRange::Integer(
+                -Transpose::MAX_OCTAVES as i32,
+                Transpose::MAX_OCTAVES as i32,
+            ),
+            ParamId::Semitones => ParamRange::Integer(
+                -Transpose::MAX_SEMITONES as i32,
+                Transpose::MAX_SEMITONES as i32,
+            ),
+            ParamId::MasterTune => ParamRange::Linear(-Transpose::MAX_CENTS, Transpose::MAX_CENTS),
+            ParamId::Waveform => ParamRange::Wavetable,
+            ParamId::Interpolation => ParamRange::Choice(Interpolation::ALL.len()),
+            ParamId::OscillatorPan => ParamRange::Linear(-1.0, 1.0),
+            ParamId::Position => ParamRange::Linear(0.0, 1.0),
+            ParamId::PositionModSource => ParamRange::Choice(ModSource::ALL.len()),
+            ParamId::PositionModAmount => ParamRange::Linear(-1.0, 1.0),
+            ParamId::LfoRate => ParamRange::Logarithmic(0.01, 20.0),
+            ParamId::Tempo => ParamRange::Linear(MIN_TEMPO_BPM, MAX_TEMPO_BPM),
+            ParamId::DriveEnabled => ParamRange::Toggle,
+            ParamId::DriveCurve => ParamRange::Choice(DriveCurve::ALL.len()),
+            ParamId::DriveGain => ParamRange::Linear(0.0, MAX_DRIVE_DB),
+            ParamId::DriveFeedback => ParamRange::Linear(0.0, 1.0),
+            ParamId::DriveOversampling => ParamRange::Choice(Oversampling::ALL.len()),
+            ParamId::ChorusMode => ParamRange::Choice(ChorusMode::ALL.len()),
+            ParamId::ChorusRate => ParamRange::Logarithmic(0.05, MAX_CHORUS_RATE_HZ),
+            ParamId::ChorusDepth => ParamRange::Linear(0.0, 1.0),
+            ParamId::ChorusVoices => ParamRange::Integer(1, MAX_CHORUS_VOICES as i32),
+            ParamId::ChorusMix => ParamRange::Linear(0.0, 1.0),
+            ParamId::DelayTime => ParamRange::Logarithmic(1.0, MAX_DELAY_MS),
+            ParamId::DelayFeedback => ParamRange::Linear(0.0, MAX_FEEDBACK),
+            ParamId::DelayTone => ParamRange::Logarithmic(200.0, 20000.0),
+            ParamId::DelayPingPong => ParamRange::Toggle,
+            ParamId::DelayMix => ParamRange::Linear(0.0, 1.0),
+            ParamId::ReverbSize => ParamRange::Linear(0.1, 1.0),
+            ParamId::ReverbDecay => ParamRange::Logarithmic(0.1, MAX_DECAY_S),
+            ParamId::ReverbDamping => ParamRange::Linear(0.0, 1.0),
+            ParamId::ReverbPreDelay => ParamRange::Linear(0.0, MAX_PRE_DELAY_MS),
+            ParamId::ReverbMix => ParamRange::Linear(0.0, 1.0),
+            ParamId::Master => ParamRange::Linear(0.0, 1.0),
+            ParamId::Pan => ParamRange::Linear(-1.0, 1.0),
+            ParamId::Width => ParamRange::Linear(0.0, MAX_WIDTH),
+            ParamId::Ceiling => ParamRange::Linear(MIN_CEILING_DB, 0.0),
+            ParamId::Attack | ParamId::Decay | ParamId::Release => {
+                ParamRange::Logarithmic(5.0, 10000.0)
+            }
+            ParamId::Sustain => ParamRange::Linear(0.0, 1.0),
+        }
+    }
+    // writes `value`, in the units of the range, into the patch and returns the event that
+    // brings the audio thread along
+    // the transpose is not part of the patch and the wavetables depend on what was imported,
+    // those are left to the caller
+    pub fn set(&self, patch: &mut Patch, value: f32) -> Option<Event> {
+        let index = value as usize;
+        let on = value > 0.5;
+        match self {
+            ParamId::VoiceMode => {
+                patch.voice_mode = VoiceMode::ALL[index];
+                Some(Event::SetVoiceMode(patch.voice_mode))
+            }
+            ParamId::Polyphony => {
+                patch.polyphony = index;
+                Some(Event::SetPolyphony(patch.polyphony))
+            }
+            ParamId::VoiceStealing => {
+                patch.voice_stealing = VoiceStealing::ALL[index];
+                Some(Event::SetVoiceStealing(patch.voice_stealing))
+            }
+            ParamId::UnisonVoices => {
+                patch.unison_voices = index;
+                Some(Event::SetUnisonVoices(patch.unison_voices))
+            }
+            ParamId::UnisonDetune => {
+                patch.unison_detune = value;
+                Some(Event::SetUnisonDetune(patch.unison_detune))
+            }
+            ParamId::UnisonSpread => {
+                patch.unison_spread = value;
+                Some(Event::SetUnisonSpread(patch.unison_spread))
+            }
+            ParamId::UnisonPhase => {
+                patch.unison_phase = UnisonPhase::ALL[index];
+                Some(Event::SetUnisonPhase(patch.unison_phase))
+            }
+            ParamId::Interpolation => {
+                patch.interpolation = Interpolation::ALL[index];
+                Some(Event::SetInterpolation(patch.interpolation))
+            }
+            ParamId::OscillatorPan => {
+                patch.oscillator_pan = value;
+                Some(Event::SetOscillatorPan(patch.oscillator_pan))
+            }
+            ParamId::Position => {
+                patch.position = value;
+                Some(Event::SetPosition(patch.position))
+            }
+            ParamId::PositionModSource => {
+                patch.position_mod_source = ModSource::ALL[index];
+                Some(Event::SetPositionModSource(patch.position_mod_source))
+            }
+            ParamId::PositionModAmount => {
+                patch.position_mod_amount = value;
+                Some(Event::SetPositionModAmount(patch.position_mod_amount))
+            }
+            ParamId::LfoRate => {
+                patch.lfo_rate = value;
+                Some(Event::SetLfoRate(patch.lfo_rate))
+            }
+            ParamId::Tempo => {
+                patch.tempo = value;
+                Some(Event::SetTempo(patch.tempo))
+            }
+            ParamId::DriveEnabled => {
+                patch.drive.enabled = on;
+                Some(Event::SetDriveEnabled(patch.drive.enabled))
+            }
+            ParamId::DriveCurve => {
+                patch.drive.curve = DriveCurve::ALL[index];
+                Some(Event::SetDriveCurve(patch.drive.curve))
+            }
+            ParamId::DriveGain => {
+                patch.drive.drive_db = value;
+                Some(Event::SetDriveGain(patch.drive.drive_db))
+            }
+            ParamId::DriveFeedback => {
+                patch.drive.feedback = value;
+                Some(Event::SetDriveFeedback(patch.drive.feedback))
+            }
+            ParamId::DriveOversampling => {
+                patch.drive.oversampling = Oversampling::ALL[index];
+                Some(Event::SetDriveOversampling(patch.drive.oversampling))
+            }
+            ParamId::ChorusMode => {
+                patch.chorus.mode = ChorusMode::ALL[index];
+                Some(Event::SetChorusMode(patch.chorus.mode))
+            }
+            ParamId::ChorusRate => {
+                patch.chorus.rate_hz = value;
+                Some(Event::SetChorusRate(patch.chorus.rate_hz))
+            }
+            ParamId::ChorusDepth => {
+                patch.chorus.depth = value;
+                Some(Event::SetChorusDepth(patch.chorus.depth))
+            }
+            ParamId::ChorusVoices => {
+                patch.chorus.voices = index;
+                Some(Event::SetChorusVoices(patch.chorus.voices))
+            }
+            ParamId::ChorusMix => {
+                patch.chorus.mix = value;
+                Some(Event::SetChorusMix(patch.chorus.mix))
+            }
+            ParamId::DelayTime => {
+                patch.delay.time = DelayTime::Ms(value);
+                Some(Event::SetDelayTime(patch.delay.time))
+            }
+            ParamId::DelayFeedback => {
+                patch.delay.feedback = value;
+                Some(Event::SetDelayFeedback(patch.delay.feedback))
+            }
+            ParamId::DelayTone => {
+                patch.delay.tone_hz = value;
+                Some(Event::SetDelayTone(patch.delay.tone_hz))
+            }
+            ParamId::DelayPingPong => {
+                patch.delay.ping_pong = on;
+                Some(Event::SetDelayPingPong(patch.delay.ping_pong))
+            }
+            ParamId::DelayMix => {
+                patch.delay.mix = value;
+                Some(Event::SetDelayMix(patch.delay.mix))
+            }
+            ParamId::ReverbSize => {
+                patch.reverb.size = value;
+                Some(Event::SetReverbSize(patch.reverb.size))
+            }
+            ParamId::ReverbDecay => {
+                patch.reverb.decay_s = value;
+                Some(Event::SetReverbDecay(patch.reverb.decay_s))
+            }
+            ParamId::ReverbDamping => {
+                patch.reverb.damping = value;
+                Some(Event::SetReverbDamping(patch.reverb.damping))
+            }
+            ParamId::ReverbPreDelay => {
+                patch.reverb.pre_delay_ms = value;
+                Some(Event::SetReverbPreDelay(patch.reverb.pre_delay_ms))
+            }
+            ParamId::ReverbMix => {
+                patch.reverb.mix = value;
+                Some(Event::SetReverbMix(patch.reverb.mix))
+            }
+            ParamId::Master => {
+                patch.master = value;
+                Some(Event::SetMaster(patch.master))
+            }
+            ParamId::Pan => {
+                patch.pan = value;
+                Some(Event::SetPan(patch.pan))
+            }
+            ParamId::Width => {
+                patch.width = value;
+                Some(Event::SetWidth(patch.width))
+            }
+            ParamId::Ceiling => {
+                patch.ceiling_db = value;
+                Some(Event::SetCeiling(patch.ceiling_db))
+            }
+            ParamId::Attack => {
+                patch.envelope.attack_ms = value.round() as u16;
+                Some(Event::SetAttackMs(patch.envelope.attack_ms))
+            }
+            ParamId::Decay => {
+                patch.envelope.decay_ms = value.round() as u16;
+                Some(Event::SetDecayMs(patch.envelope.decay_ms))
+            }
+            ParamId::Sustain => {
+                patch.envelope.sustain = value;
+                Some(Event::SetSustain(patch.envelope.sustain))
+            }
+            ParamId::Release => {
+                patch.envelope.release_ms = value.round() as u16;
+                Some(Event::SetReleaseMs(patch.envelope.release_ms))
+            }
+            ParamId::Octave | ParamId::Semitones | ParamId::MasterTune | ParamId::Waveform => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            ParamId::VoiceMode => "Voice mode",
+            ParamId::Polyphony => "Polyphony",
+            ParamId::VoiceStealing => "Voice stealing",
+            ParamId::UnisonVoices => "Unison voices",
+            ParamId::UnisonDetune => "Unison detune",
+            ParamId::UnisonSpread => "Unison spread",
+            ParamId::UnisonPhase => "Unison start phase",
+            ParamId::Octave => "Octave",
+            ParamId::Semitones => "Semitones",
+            ParamId::MasterTune => "Master tune",
+            ParamId::Waveform => "Waveform",
+            ParamId::Interpolation => "Interpolation",
+            ParamId::OscillatorPan => "Oscillator pan",
+            ParamId::Position => "Position",
+            ParamId::PositionModSource => "Position mod source",
+            ParamId::PositionModAmount => "Position mod amount",
+            ParamId::LfoRate => "LFO rate",
+            ParamId::Tempo => "Tempo",
+            ParamId::DriveEnabled => "Drive on",
+            ParamId::DriveCurve => "Drive curve",
+            ParamId::DriveGain => "Drive gain",
+            ParamId::DriveFeedback => "Drive feedback",
+            ParamId::DriveOversampling => "Drive oversampling",
+            ParamId::ChorusMode => "Chorus mode",
+            ParamId::ChorusRate => "Chorus rate",
+            ParamId::ChorusDepth => "Chorus depth",
+            ParamId::ChorusVoices => "Chorus voices",
+            ParamId::ChorusMix => "Chorus mix",
+            ParamId::DelayTime => "Delay time",
+            ParamId::DelayFeedback => "Delay feedback",
+            ParamId::DelayTone => "Delay tone",
+            ParamId::DelayPingPong => "Delay ping-pong",
+            ParamId::DelayMix => "Delay mix",
+            ParamId::ReverbSize => "Reverb size",
+            ParamId::ReverbDecay => "Reverb decay",
+            ParamId::ReverbDamping => "Reverb damping",
+            ParamId::ReverbPreDelay => "Reverb pre-delay",
+            ParamId::ReverbMix => "Reverb mix",
+            ParamId::Master => "Master volume",
+            ParamId::Pan => "Pan",
+            ParamId::Width => "Width",
+            ParamId::Ceiling => "Ceiling",
+            ParamId::Attack => "Attack",
+            ParamId::Decay => "Decay",
+            ParamId::Sustain => "Sustain",
+            ParamId::Release => "Release",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+// the patch and transpose that parameters are set on when there is no app to hold them,
+// from a script or a controller on the command line
+// the waveform only picks between the built-in wavetables there
+pub struct ParamState {
+    pub patch: Patch,
+    pub transpose: Transpose,
+}
+
+impl ParamState {
+    pub fn new(patch: Patch) -> Self {
+        Self {
+            patch,
+            transpose: Transpose::default(),
+        }
+    }
+
+    // `value` is in the units of the parameter's range and clamped to it
+    pub fn set(&mut self, param: ParamId, value: f32) -> Option<Event> {
+        let value = param.range().clamp(value);
+        match param {
+            ParamId::Octave => self.transpose.octaves = value as i8,
+            ParamId::Semitones => self.transpose.semitones = value as i8,
+            ParamId::MasterTune => self.transpose.cents = value,
+            ParamId::Waveform => {
+                let index = (value as usize).min(BUILT_IN_WAVEFORMS.len() - 1);
+                self.patch.wavetable = BUILT_IN_WAVEFORMS[index];
+                return Some(Event::ChangeOscillator(self.patch.wavetable));
+            }
+            _ => return param.set(&mut self.patch, value),
+        }
+        Some(Event::SetTranspose(self.transpose))
+    }
+}
@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::Event;
+use crate::synth::{
+    ChorusParams, DelayParams, DriveParams, Envelope, Interpolation, KeyboardMapping, ModSource,
+    MtsTable, ReverbParams, Scale, UnisonPhase, VoiceMode, VoiceStealing, WavetableKind,
+    DEFAULT_CEILING_DB, DEFAULT_TEMPO_BPM,
+};
+
+// everything needed to recall a sound, stored as JSON
+// missing fields take their default value so that older patches keep loading
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Patch {
+    pub voice_mode: VoiceMode,
+    pub polyphony: usize,
+    pub voice_stealing: VoiceStealing,
+    pub unison_voices: usize,
+    pub unison_detune: f32,
+    pub unison_spread: f32,
+    pub unison_phase: UnisonPhase,
+    pub wavetable: WavetableKind,
+    // where a user wavetable was imported from, its `WavetableKind::User` index only holds
+    // for the session it was imported in
+    pub wavetable_file: Option<PathBuf>,
+    pub interpolation: Interpolation,
+    pub oscillator_pan: f32,
+    pub position: f32,
+    pub position_mod_source: ModSource,
+    pub position_mod_amount: f32,
+    pub lfo_rate: f32,
+    pub tempo: f32,
+    pub drive: DriveParams,
+    pub chorus: ChorusParams,
+    pub delay: DelayParams,
+    pub reverb: ReverbParams,
+    pub master: f32,
+    pub pan: f32,
+    pub width: f32,
+    pub ceiling_db: f32,
+    pub envelope: Envelope,
+    pub scale: Scale,
+    pub mapping: KeyboardMapping,
+    pub retuning: MtsTable,
+}
+
+impl Default for Patch {
+    fn default() -> Self {
+        Self {
+            voice_mode: VoiceMode::default(),
+            polyphony: 8,
+            voice_stealing: VoiceStealing::default(),
+            unison_voices: 1,
+            unison_detune: 0.0,
+            unison_spread: 0.0,
+            unison_phase: UnisonPhase::default(),
+            wavetable: WavetableKind::Triangle,
+            wavetable_file: None,
+            interpolation: Interpolation::default(),
+            oscillator_pan: 0.0,
+            position: 0.0,
+            position_mod_source: ModSource::default(),
+            position_mod_amount: 0.0,
+            lfo_rate: 1.0,
+            tempo: DEFAULT_TEMPO_BPM,
+            drive: DriveParams::default(),
+            chorus: ChorusParams::default(),
+            delay: DelayParams::default(),
+            reverb: ReverbParams::default(),
+            master: 0.7,
+            pan: 0.0,
+            width: 1.0,
+            ceiling_db: DEFAULT_CEILING_DB,
+            envelope: Envelope::default(),
+            scale: Scale::equal_temperament(12),
+            mapping: KeyboardMapping::default(),
+            retuning: MtsTable::default(),
+        }
+    }
+}
+
+impl Patch {
+    pub fn from_disk(path: impl AsRef<Path>) -> Result<Self, PatchError> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(PatchError::Io)
+            .and_then(|text| serde_json::from_str(&text).map_err(PatchError::Json))
+            .map_err(|err| PatchError::InFile(path.to_path_buf(), Box::new(err)))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PatchError> {
+        let path = path.as_ref();
+        serde_json::to_string_pretty(self)
+            .map_err(PatchError::Json)
+            .and_then(|text| std::fs::write(path, text).map_err(PatchError::Io))
+            .map_err(|err| PatchError::InFile(path.to_path_buf(), Box::new(err)))
+    }
+
+    // brings the audio thread in line with the patch
+    pub fn events(&self) -> Vec<Event> {
+        vec![
+            Event::SetVoiceMode(self.voice_mode),
+            Event::SetPolyphony(self.polyphony),
+            Event::SetVoiceStealing(self.voice_stealing),
+            Event::SetUnisonVoices(self.unison_voices),
+            Event::SetUnisonDetune(self.unison_detune),
+            Event::SetUnisonSpread(self.unison_spread),
+            Event::SetUnisonPhase(self.unison_phase),
+            Event::ChangeOscillator(self.wavetable),
+            Event::SetInterpolation(self.interpolation),
+            Event::SetOscillatorPan(self.oscillator_pan),
+            Event::SetPosition(self.position),
+            Event::SetPositionModSource(self.position_mod_source),
+            Event::SetPositionModAmount(self.position_mod_amount),
+            Event::SetLfoRate(self.lfo_rate),
+            Event::SetTempo(self.tempo),
+            Event::SetDriveEnabled(self.drive.enabled),
+            Event::SetDriveCurve(self.drive.curve),
+            Event::SetDriveGain(self.drive.drive_db),
+            Event::SetDriveFeedback(self.drive.feedback),
+            Event::SetDriveOversampling(self.drive.oversampling),
+            Event::SetChorusMode(self.chorus.mode),
+            Event::SetChorusRate(self.chorus.rate_hz),
+            Event::SetChorusDepth(self.chorus.depth),
+            Event::SetChorusVoices(self.chorus.voices),
+            Event::SetChorusMix(self.chorus.mix),
+            Event::SetDelayTime(self.delay.time),
+            Event::SetDelayFeedback(self.delay.feedback),
+            Event::SetDelayTone(self.delay.tone_hz),
+            Event::SetDelayPingPong(self.delay.ping_pong),
+            Event::SetDelayMix(self.delay.mix),
+            Event::SetReverbSize(self.reverb.size),
+            Event::SetReverbDecay(self.reverb.decay_s),
+            Event::SetReverbDamping(self.reverb.damping),
+            Event::SetReverbPreDelay(self.reverb.pre_delay_ms),
+            Event::SetReverbMix(self.reverb.mix),
+            Event::SetMaster(self.master),
+            Event::SetPan(self.pan),
+            Event::SetWidth(self.width),
+            Event::SetCeiling(self.ceiling_db),
+            Event::SetAttackMs(self.envelope.attack_ms),
+            Event::SetDecayMs(self.envelope.decay_ms),
+            Event::SetSustain(self.envelope.sustain),
+            Event::SetReleaseMs(self.envelope.release_ms),
+            Event::SetScale(Arc::new(self.scale.clone())),
+            Event::SetKeyboardMapping(Arc::new(self.mapping.clone())),
+            Event::SetRetuning(self.retuning.clone()),
+        ]
+    }
+}
+
+#[derive(Debug)]
+pub enum PatchError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    InFile(PathBuf, Box<PatchError>),
+}
+
+impl std::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::Io(err) => write!(f, "{}", err),
+            PatchError::Json(err) => write!(f, "{}", err),
+            PatchError::InFile(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
@@ -0,0 +1,167 @@
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::event::Event;
+use crate::midi::MidiNote;
+use crate::synth::VoiceState;
+
+const HEIGHT: f32 = 90.0;
+const BLACK_WIDTH: f32 = 0.6;
+const BLACK_HEIGHT: f32 = 0.6;
+// below the keys, marks the notes the computer keyboard plays
+const RANGE_BAR_HEIGHT: f32 = 6.0;
+
+// an on-screen keyboard played with the mouse
+// pressing lower on a key plays it louder, dragging across keys plays them in turn
+pub struct Piano {
+    first_note: MidiNote,
+    octaves: u8,
+    // the note held down with the mouse
+    held: Option<MidiNote>,
+}
+
+impl Default for Piano {
+    fn default() -> Self {
+        Self {
+            first_note: MidiNote::c(1),
+            octaves: 6,
+            held: None,
+        }
+    }
+}
+
+impl Piano {
+    fn last_note(&self) -> MidiNote {
+        // up to the C above the last octave
+        self.first_note.octave_up(self.octaves)
+    }
+
+    fn notes(&self) -> impl Iterator<Item = MidiNote> {
+        (self.first_note.note..=self.last_note().note).map(|note| MidiNote { note })
+    }
+
+    // `sounding` comes from the audio thread, so it includes notes from every source
+    // `range` is the span of the computer keyboard
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        sounding: &[(VoiceState, f32)],
+        range: (MidiNote, MidiNote),
+    ) -> Vec<Event> {
+        let size = Vec2::new(ui.available_width(), HEIGHT + RANGE_BAR_HEIGHT);
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let keys_rect = Rect::from_min_size(response.rect.min, Vec2::new(size.x, HEIGHT));
+        let keys: Vec<(MidiNote, Rect)> = self
+            .notes()
+            .map(|note| (note, self.key_rect(keys_rect, note)))
+            .collect();
+
+        let mut events = Vec::new();
+        let pressed = response
+            .is_pointer_button_down_on()
+            .then(|| response.interact_pointer_pos())
+            .flatten()
+            .and_then(|position| hit_test(&keys, position));
+        if pressed.map(|(note, _)| note) != self.held {
+            if let Some(note) = self.held.take() {
+                events.push(Event::NoteOff(note));
+            }
+            if let Some((note, velocity)) = pressed {
+                events.push(Event::NoteOn(note, velocity));
+                self.held = Some(note);
+            }
+        }
+
+        let state_of = |note: MidiNote| {
+            sounding
+                .iter()
+                .filter(|(state, _)| state.get_note() == Some(note))
+                .map(|(state, _)| matches!(state, VoiceState::Releasing(_)))
+                .min()
+        };
+        let font = FontId::proportional(10.0);
+        // white keys first, the black ones are drawn over them
+        for black in [false, true] {
+            for (note, rect) in keys.iter().filter(|(note, _)| is_black(*note) == black) {
+                let fill = match (state_of(*note), black) {
+                    (Some(false), _) => Color32::from_rgb(230, 180, 60),
+                    (Some(true), _) => Color32::from_rgb(150, 120, 50),
+                    (None, false) => Color32::from_gray(235),
+                    (None, true) => Color32::from_gray(30),
+                };
+                painter.rect(
+                    *rect,
+                    2.0,
+                    fill,
+                    Stroke::new(1.0, Color32::from_gray(80)),
+                    egui::StrokeKind::Inside,
+                );
+                if note.note % 12 == 0 {
+                    painter.text(
+                        Pos2::new(rect.center().x, rect.bottom() - 3.0),
+                        Align2::CENTER_BOTTOM,
+                        format!("{note}"),
+                        font.clone(),
+                        Color32::from_gray(90),
+                    );
+                }
+            }
+        }
+
+        let (low, high) = range;
+        let span: Vec<&Rect> = keys
+            .iter()
+            .filter(|(note, _)| (low.note..=high.note).contains(&note.note))
+            .map(|(_, rect)| rect)
+            .collect();
+        if let (Some(first), Some(last)) = (span.first(), span.last()) {
+            let bar = Rect::from_min_max(
+                Pos2::new(first.left(), keys_rect.bottom() + 2.0),
+                Pos2::new(last.right(), keys_rect.bottom() + RANGE_BAR_HEIGHT),
+            );
+            painter.rect_filled(bar, 2.0, Color32::from_rgb(90, 140, 220));
+        }
+
+        events
+    }
+
+    fn key_rect(&self, rect: Rect, note: MidiNote) -> Rect {
+        let whites = self.notes().filter(|note| !is_black(*note)).count();
+        let white_width = rect.width() / whites as f32;
+        let whites_before = (self.first_note.note..note.note)
+            .filter(|note| !is_black(MidiNote { note: *note }))
+            .count();
+        let x = rect.left() + whites_before as f32 * white_width;
+
+        if is_black(note) {
+            let width = BLACK_WIDTH * white_width;
+            Rect::from_min_size(
+                Pos2::new(x - 0.5 * width, rect.top()),
+                Vec2::new(width, BLACK_HEIGHT * rect.height()),
+            )
+        } else {
+            Rect::from_min_size(
+                Pos2::new(x, rect.top()),
+                Vec2::new(white_width, rect.height()),
+            )
+        }
+    }
+}
+
+fn is_black(note: MidiNote) -> bool {
+    matches!(note.note % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+// black keys sit on top, so they win; velocity grows towards the front of the key
+fn hit_test(keys: &[(MidiNote, Rect)], position: Pos2) -> Option<(MidiNote, u8)> {
+    let (note, rect) = keys
+        .iter()
+        .filter(|(note, rect)| is_black(*note) && rect.contains(position))
+        .chain(
+            keys.iter()
+                .filter(|(note, rect)| !is_black(*note) && rect.contains(position)),
+        )
+        .next()?;
+    let depth = ((position.y - rect.top()) / rect.height()).clamp(0.0, 1.0);
+
+    Some((*note, 1 + (126.0 * depth).round() as u8))
+}
@@ -0,0 +1,116 @@
+use egui::{Color32, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::synth::{OutputMonitor, MONITOR_CAPACITY};
+
+pub const MIN_TIMEBASE_MS: f32 = 1.0;
+pub const MAX_TIMEBASE_MS: f32 = 200.0;
+
+// the synth's output over time, triggered on a rising zero crossing so that periodic
+// waveforms stand still
+pub struct Scope {
+    // the width of the display
+    timebase_ms: f32,
+    trigger: bool,
+    frozen: bool,
+    buffer: Vec<f32>,
+    trace: Vec<f32>,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            timebase_ms: 10.0,
+            trigger: true,
+            frozen: false,
+            buffer: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+}
+
+impl Scope {
+    pub fn show(&mut self, ui: &mut egui::Ui, monitor: &OutputMonitor, sample_rate: f32) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::Slider::new(&mut self.timebase_ms, MIN_TIMEBASE_MS..=MAX_TIMEBASE_MS)
+                    .logarithmic(true)
+                    .text("Timebase (ms)"),
+            );
+            ui.checkbox(&mut self.trigger, "Trigger");
+            ui.checkbox(&mut self.frozen, "Freeze");
+        });
+
+        if !self.frozen {
+            self.capture(monitor, sample_rate);
+        }
+
+        let size = Vec2::new(ui.available_width(), ui.available_height().max(120.0));
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, Color32::from_gray(16));
+        let center = rect.center().y;
+        painter.hline(
+            rect.x_range(),
+            center,
+            Stroke::new(1.0, Color32::from_gray(60)),
+        );
+        draw_trace(&painter, rect, &self.trace);
+    }
+
+    fn capture(&mut self, monitor: &OutputMonitor, sample_rate: f32) {
+        let window =
+            ((self.timebase_ms * sample_rate / 1000.0) as usize).clamp(2, MONITOR_CAPACITY / 2);
+        // twice the window, the trigger is looked for in the older half
+        self.buffer.resize(2 * window, 0.0);
+        monitor.read_latest(&mut self.buffer);
+
+        let start = if self.trigger {
+            // the most recent crossing that still leaves a full window after it
+            (1..=window)
+                .rev()
+                .find(|&i| self.buffer[i - 1] < 0.0 && self.buffer[i] >= 0.0)
+                .unwrap_or(window)
+        } else {
+            window
+        };
+        self.trace.clear();
+        self.trace
+            .extend_from_slice(&self.buffer[start..start + window]);
+    }
+}
+
+// one vertical stroke per pixel column with the range of the samples it covers,
+// so that dense traces keep their peaks
+fn draw_trace(painter: &egui::Painter, rect: Rect, trace: &[f32]) {
+    if trace.len() < 2 {
+        return;
+    }
+    let stroke = Stroke::new(1.5, Color32::LIGHT_GREEN);
+    let y = |sample: f32| rect.center().y - 0.5 * rect.height() * sample.clamp(-1.0, 1.0);
+    let columns = rect.width().max(1.0) as usize;
+
+    if trace.len() <= 2 * columns {
+        let points = trace
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| {
+                let x = rect.left() + rect.width() * i as f32 / (trace.len() - 1) as f32;
+                Pos2::new(x, y(*sample))
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, stroke));
+        return;
+    }
+
+    for column in 0..columns {
+        let from = column * trace.len() / columns;
+        let to = ((column + 1) * trace.len() / columns).max(from + 1);
+        let (low, high) = trace[from..to]
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(low, high), sample| {
+                (low.min(*sample), high.max(*sample))
+            });
+        let x = rect.left() + column as f32;
+        painter.line_segment([Pos2::new(x, y(high)), Pos2::new(x, y(low) + 1.0)], stroke);
+    }
+}
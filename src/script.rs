@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use crate::event::Event;
+use crate::midi::{MidiNote, DEFAULT_VELOCITY};
+use crate::params::{ParamId, ParamState};
+
+// a score to render, one event per line after its time in seconds:
+//   0.0  on C4 [velocity]
+//   1.5  off C4
+//   1.5  set position 0.5
+// notes are MIDI numbers or names, C4 is 60; parameters go by the names the config uses for
+// MIDI mappings and their values are in the parameter's own units
+// a `#` at the start of a word starts a comment, sharps like `C#4` don't
+pub fn read(
+    path: impl AsRef<Path>,
+    params: &mut ParamState,
+) -> Result<Vec<(f64, Event)>, ScriptError> {
+    let path = path.as_ref();
+    std::fs::read_to_string(path)
+        .map_err(ScriptError::Io)
+        .and_then(|text| parse(&text, params))
+        .map_err(|err| ScriptError::InFile(path.to_path_buf(), Box::new(err)))
+}
+
+// `params` follows the `set` lines, in the order they are written
+pub fn parse(text: &str, params: &mut ParamState) -> Result<Vec<(f64, Event)>, ScriptError> {
+    let mut events = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line
+            .split_whitespace()
+            .take_while(|word| !word.starts_with('#'))
+            .collect();
+        if words.is_empty() {
+            continue;
+        }
+        let error = |message: String| ScriptError::Line(index + 1, message);
+
+        let time: f64 = words[0]
+            .parse()
+            .ok()
+            .filter(|time: &f64| time.is_finite() && *time >= 0.0)
+            .ok_or_else(|| error(format!("bad time {:?}", words[0])))?;
+        let event = match words[1..] {
+            ["on", note] => Event::NoteOn(parse_note(note).map_err(error)?, DEFAULT_VELOCITY),
+            ["on", note, velocity] => {
+                let velocity = velocity
+                    .parse()
+                    .ok()
+                    .filter(|velocity| (1..=127).contains(velocity))
+                    .ok_or_else(|| error(format!("bad velocity {:?}", velocity)))?;
+                Event::NoteOn(parse_note(note).map_err(error)?, velocity)
+            }
+            ["off", note] => Event::NoteOff(parse_note(note).map_err(error)?),
+            ["set", name, value] => {
+                let param: ParamId =
+                    serde_json::from_value(serde_json::Value::String(name.to_string()))
+                        .map_err(|_| error(format!("unknown parameter {:?}", name)))?;
+                let value = value
+                    .parse()
+                    .map_err(|_| error(format!("bad value {:?}", value)))?;
+                match params.set(param, value) {
+                    Some(event) => event,
+                    None => continue,
+                }
+            }
+            _ => return Err(error(format!("can't read {:?}", words.join(" ")))),
+        };
+        events.push((time, event));
+    }
+    // lines at the same time keep their order
+    events.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+    Ok(events)
+}
+
+fn parse_note(text: &str) -> Result<MidiNote, String> {
+    const NAMES: [(char, i16); 7] = [
+        ('C', 0),
+        ('D', 2),
+        ('E', 4),
+        ('F', 5),
+        ('G', 7),
+        ('A', 9),
+        ('B', 11),
+    ];
+    let note = text.parse::<i16>().ok().or_else(|| {
+        let mut chars = text.chars();
+        let letter = chars.next()?.to_ascii_uppercase();
+        let (_, mut note) = NAMES.into_iter().find(|(name, _)| *name == letter)?;
+        let rest = chars.as_str();
+        let octave = if let Some(rest) = rest.strip_prefix('#') {
+            note += 1;
+            rest
+        } else if let Some(rest) = rest.strip_prefix('b') {
+            note -= 1;
+            rest
+        } else {
+            rest
+        };
+        Some(note + (octave.parse::<i16>().ok()? + 1) * 12)
+    });
+    match note {
+        Some(note @ 0..=127) => Ok(MidiNote { note: note as u8 }),
+        _ => Err(format!("bad note {:?}", text)),
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(std::io::Error),
+    // with the line number, from 1
+    Line(usize, String),
+    InFile(PathBuf, Box<ScriptError>),
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "{}", err),
+            ScriptError::Line(line, message) => write!(f, "line {}: {}", line, message),
+            ScriptError::InFile(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Patch;
+
+    fn parse_default(text: &str) -> Result<Vec<(f64, Event)>, ScriptError> {
+        parse(text, &mut ParamState::new(Patch::default()))
+    }
+
+    #[test]
+    fn note_names() {
+        for (name, note) in [
+            ("C4", 60),
+            ("c4", 60),
+            ("C#4", 61),
+            ("Bb3", 58),
+            ("A4", 69),
+            ("C-1", 0),
+            ("G9", 127),
+            ("64", 64),
+        ] {
+            assert_eq!(parse_note(name), Ok(MidiNote { note }), "{name}");
+        }
+        for name in ["H4", "C", "G#9", "Cb-1", "128", "-1", "C4x"] {
+            assert!(parse_note(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn note_lines() {
+        let events = parse_default("0 on C#4\n0.5 on Bb3 64 # quieter\n1 off C#4\n").unwrap();
+        assert!(matches!(
+            events[..],
+            [
+                (0.0, Event::NoteOn(MidiNote { note: 61 }, DEFAULT_VELOCITY)),
+                (0.5, Event::NoteOn(MidiNote { note: 58 }, 64)),
+                (1.0, Event::NoteOff(MidiNote { note: 61 })),
+            ]
+        ));
+    }
+
+    #[test]
+    fn set_lines_follow_the_parameters() {
+        let mut params = ParamState::new(Patch::default());
+        let events = parse("0 set position 0.25\n1 set position 2\n", &mut params).unwrap();
+        // in the parameter's range, like from a controller
+        assert!(matches!(
+            events[..],
+            [
+                (0.0, Event::SetPosition(position)),
+                (1.0, Event::SetPosition(clamped)),
+            ] if position == 0.25 && clamped == 1.0
+        ));
+        assert_eq!(params.patch.position, 1.0);
+    }
+
+    #[test]
+    fn lines_at_the_same_time_keep_their_order() {
+        let events = parse_default("1 on E4\n0 on C4\n1 off C4\n0 on D4\n1 off D4\n").unwrap();
+        let order: Vec<(f64, u8)> = events
+            .iter()
+            .map(|(time, event)| match event {
+                Event::NoteOn(note, _) => (*time, note.note),
+                Event::NoteOff(note) => (*time, note.note + 100),
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [(0.0, 60), (0.0, 62), (1.0, 64), (1.0, 160), (1.0, 162)]
+        );
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        for (text, line) in [
+            ("0 on C4\n\n-1 off C4\n", 3),
+            ("# comment\n0 on C4 0\n", 2),
+            ("0 set cutoff 1\n", 1),
+            ("0 set position loud\n", 1),
+            ("0 play C4\n", 1),
+        ] {
+            match parse_default(text) {
+                Err(ScriptError::Line(found, _)) => assert_eq!(found, line, "{text:?}"),
+                other => panic!("{text:?}: {other:?}"),
+            }
+        }
+    }
+}
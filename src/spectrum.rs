@@ -0,0 +1,243 @@
+use std::f32::consts::TAU;
+
+use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Stroke, Vec2};
+
+use crate::math::{self, Complex};
+use crate::synth::OutputMonitor;
+
+const MIN_FREQUENCY: f32 = 20.0;
+const MIN_DB: f32 = -120.0;
+pub const FFT_SIZES: [usize; 5] = [1024, 2048, 4096, 8192, 16384];
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum Window {
+    Rectangular,
+    #[default]
+    Hann,
+    Blackman,
+    // sidelobes below -90 dB, for looking at aliasing under a loud fundamental
+    BlackmanHarris,
+}
+
+impl Window {
+    pub const ALL: [Window; 4] = [
+        Window::Rectangular,
+        Window::Hann,
+        Window::Blackman,
+        Window::BlackmanHarris,
+    ];
+
+    // `x` goes from 0 to 1 over the frame
+    fn at(&self, x: f32) -> f32 {
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * (TAU * x).cos(),
+            Window::Blackman => math::blackman(2.0 * x - 1.0),
+            Window::BlackmanHarris => {
+                0.35875 - 0.48829 * (TAU * x).cos() + 0.14128 * (2.0 * TAU * x).cos()
+                    - 0.01168 * (3.0 * TAU * x).cos()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            Window::Rectangular => "Rectangular",
+            Window::Hann => "Hann",
+            Window::Blackman => "Blackman",
+            Window::BlackmanHarris => "Blackman-Harris",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+// magnitude spectrum of the output, a full-scale sine reads 0 dB
+pub struct Spectrum {
+    fft_size: usize,
+    window: Window,
+    peak_hold: bool,
+    frozen: bool,
+    samples: Vec<f32>,
+    bins: Vec<Complex>,
+    // per bin, in dB
+    magnitudes: Vec<f32>,
+    peaks: Vec<f32>,
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self {
+            fft_size: 4096,
+            window: Window::default(),
+            peak_hold: false,
+            frozen: false,
+            samples: Vec::new(),
+            bins: Vec::new(),
+            magnitudes: Vec::new(),
+            peaks: Vec::new(),
+        }
+    }
+}
+
+impl Spectrum {
+    pub fn show(&mut self, ui: &mut egui::Ui, monitor: &OutputMonitor, sample_rate: f32) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label("FFT size")
+                .selected_text(format!("{}", self.fft_size))
+                .show_ui(ui, |ui| {
+                    for size in FFT_SIZES {
+                        ui.selectable_value(&mut self.fft_size, size, format!("{size}"));
+                    }
+                });
+            egui::ComboBox::from_label("Window")
+                .selected_text(format!("{}", self.window))
+                .show_ui(ui, |ui| {
+                    for window in Window::ALL {
+                        ui.selectable_value(&mut self.window, window, format!("{window}"));
+                    }
+                });
+            // not in one condition, `||` would skip the button on the frame the box changes
+            let toggled = ui.checkbox(&mut self.peak_hold, "Peak hold").changed();
+            let reset = ui.button("Reset").clicked();
+            if toggled || reset {
+                self.peaks.clear();
+            }
+            ui.checkbox(&mut self.frozen, "Freeze");
+        });
+
+        if !self.frozen {
+            self.analyze(monitor);
+        }
+
+        let size = Vec2::new(ui.available_width(), ui.available_height().max(120.0));
+        let (response, painter) = ui.allocate_painter(size, Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 4.0, Color32::from_gray(16));
+        let nyquist = 0.5 * sample_rate;
+        draw_grid(&painter, rect, nyquist);
+        if self.peak_hold {
+            draw_curve(
+                &painter,
+                rect,
+                &self.peaks,
+                nyquist,
+                Color32::from_rgb(200, 120, 40),
+            );
+        }
+        draw_curve(
+            &painter,
+            rect,
+            &self.magnitudes,
+            nyquist,
+            Color32::LIGHT_BLUE,
+        );
+    }
+
+    fn analyze(&mut self, monitor: &OutputMonitor) {
+        let n = self.fft_size;
+        self.samples.resize(n, 0.0);
+        monitor.read_latest(&mut self.samples);
+
+        let mut gain = 0.0;
+        self.bins.clear();
+        for (i, sample) in self.samples.iter().enumerate() {
+            let window = self.window.at(i as f32 / n as f32);
+            gain += window;
+            self.bins.push(Complex::new(sample * window, 0.0));
+        }
+        math::fft(&mut self.bins);
+
+        self.magnitudes.clear();
+        self.magnitudes.extend(self.bins[..n / 2].iter().map(|bin| {
+            let magnitude = 2.0 * bin.norm() / gain;
+            (20.0 * magnitude.max(1e-9).log10()).max(MIN_DB)
+        }));
+
+        if !self.peak_hold {
+            return;
+        }
+        if self.peaks.len() != self.magnitudes.len() {
+            self.peaks.clone_from(&self.magnitudes);
+        }
+        for (peak, magnitude) in self.peaks.iter_mut().zip(&self.magnitudes) {
+            *peak = peak.max(*magnitude);
+        }
+    }
+}
+
+fn frequency_to_x(rect: Rect, frequency: f32, nyquist: f32) -> f32 {
+    let position = (frequency / MIN_FREQUENCY).ln() / (nyquist / MIN_FREQUENCY).ln();
+    rect.left() + rect.width() * position
+}
+
+fn db_to_y(rect: Rect, db: f32) -> f32 {
+    rect.top() + rect.height() * (db / MIN_DB).clamp(0.0, 1.0)
+}
+
+fn draw_grid(painter: &egui::Painter, rect: Rect, nyquist: f32) {
+    let stroke = Stroke::new(1.0, Color32::from_gray(50));
+    let font = FontId::monospace(10.0);
+    let text = Color32::from_gray(120);
+
+    for frequency in [
+        50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0,
+    ] {
+        if frequency >= nyquist {
+            break;
+        }
+        let x = frequency_to_x(rect, frequency, nyquist);
+        painter.vline(x, rect.y_range(), stroke);
+        let label = if frequency >= 1000.0 {
+            format!("{}k", frequency / 1000.0)
+        } else {
+            format!("{frequency}")
+        };
+        painter.text(
+            Pos2::new(x + 2.0, rect.bottom() - 2.0),
+            Align2::LEFT_BOTTOM,
+            label,
+            font.clone(),
+            text,
+        );
+    }
+    for db in (MIN_DB as i32..0).step_by(20) {
+        let y = db_to_y(rect, db as f32);
+        painter.hline(rect.x_range(), y, stroke);
+        painter.text(
+            Pos2::new(rect.left() + 2.0, y),
+            Align2::LEFT_BOTTOM,
+            format!("{db} dB"),
+            font.clone(),
+            text,
+        );
+    }
+}
+
+// the loudest bin under each pixel column, bins are spread thinly at the bottom of a log axis
+fn draw_curve(
+    painter: &egui::Painter,
+    rect: Rect,
+    magnitudes: &[f32],
+    nyquist: f32,
+    color: Color32,
+) {
+    if magnitudes.len() < 2 {
+        return;
+    }
+    let bin_width = nyquist / magnitudes.len() as f32;
+    let columns = rect.width().max(1.0) as usize;
+    let ratio = nyquist / MIN_FREQUENCY;
+
+    let mut points = Vec::with_capacity(columns);
+    for column in 0..columns {
+        let low = MIN_FREQUENCY * ratio.powf(column as f32 / columns as f32);
+        let high = MIN_FREQUENCY * ratio.powf((column + 1) as f32 / columns as f32);
+        let from = ((low / bin_width) as usize).min(magnitudes.len() - 1);
+        let to = ((high / bin_width) as usize + 1).clamp(from + 1, magnitudes.len());
+        let db = magnitudes[from..to].iter().copied().fold(MIN_DB, f32::max);
+        points.push(Pos2::new(rect.left() + column as f32, db_to_y(rect, db)));
+    }
+    painter.add(egui::Shape::line(points, Stroke::new(1.5, color)));
+}
@@ -0,0 +1,156 @@
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+use crate::synth::effects::dsp::DelayLine;
+use crate::synth::stereo::Stereo;
+
+pub const MAX_CHORUS_VOICES: usize = 4;
+pub const MAX_CHORUS_RATE_HZ: f32 = 10.0;
+// modulation depth at depth = 1
+const MAX_DEPTH_MS: f32 = 5.0;
+const BUFFER_MS: f32 = 30.0;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ChorusMode {
+    #[default]
+    Chorus,
+    // string machine style: longer taps, and a fast vibrato on top of the slow sweep
+    Ensemble,
+}
+
+impl ChorusMode {
+    pub const ALL: [ChorusMode; 2] = [ChorusMode::Chorus, ChorusMode::Ensemble];
+
+    fn base_delay_ms(&self) -> f32 {
+        match self {
+            ChorusMode::Chorus => 7.0,
+            ChorusMode::Ensemble => 12.0,
+        }
+    }
+}
+
+impl std::fmt::Display for ChorusMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            ChorusMode::Chorus => "Chorus",
+            ChorusMode::Ensemble => "Ensemble",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ChorusParams {
+    pub mode: ChorusMode,
+    pub rate_hz: f32,
+    pub depth: f32,
+    pub voices: usize,
+    pub mix: f32,
+}
+
+impl Default for ChorusParams {
+    fn default() -> Self {
+        Self {
+            mode: ChorusMode::default(),
+            rate_hz: 0.8,
+            depth: 0.5,
+            voices: 2,
+            mix: 0.0,
+        }
+    }
+}
+
+// modulated taps on a delay line per channel, spread evenly in LFO phase and across the
+// stereo field
+pub struct Chorus {
+    params: ChorusParams,
+    sample_rate: f32,
+    lines: [DelayLine; 2],
+    phase: f32,
+    vibrato_phase: f32,
+}
+
+impl Chorus {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            params: ChorusParams::default(),
+            sample_rate,
+            lines: [(); 2].map(|_| DelayLine::new((BUFFER_MS * sample_rate / 1000.0) as usize)),
+            phase: 0.0,
+            vibrato_phase: 0.0,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: ChorusMode) {
+        self.params.mode = mode;
+    }
+
+    pub fn set_rate(&mut self, rate_hz: f32) {
+        self.params.rate_hz = rate_hz.clamp(0.0, MAX_CHORUS_RATE_HZ);
+    }
+
+    pub fn set_depth(&mut self, depth: f32) {
+        self.params.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn set_voices(&mut self, voices: usize) {
+        self.params.voices = voices.clamp(1, MAX_CHORUS_VOICES);
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        // the lines stop being written at 0, do not replay what they held back then
+        if self.params.mix == 0.0 {
+            self.lines.iter_mut().for_each(DelayLine::clear);
+        }
+        self.params.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn process(&mut self, input: Stereo) -> Stereo {
+        if self.params.mix == 0.0 {
+            return input;
+        }
+
+        let [left, right] = &mut self.lines;
+        left.push(input.left);
+        right.push(input.right);
+
+        let ChorusParams {
+            mode,
+            rate_hz,
+            depth,
+            voices,
+            mix,
+        } = self.params;
+        let samples_per_ms = self.sample_rate / 1000.0;
+        let depth_ms = depth * MAX_DEPTH_MS;
+
+        let mut wet = Stereo::default();
+        for voice in 0..voices {
+            let offset = voice as f32 / voices as f32;
+            let sweep = 0.5 + 0.5 * (self.phase + TAU * offset).sin();
+            let mut delay_ms = mode.base_delay_ms() + depth_ms * sweep;
+            if mode == ChorusMode::Ensemble {
+                delay_ms += 0.1 * depth_ms * (self.vibrato_phase + TAU * offset).sin();
+            }
+            // a single voice sits in the middle, more alternate from left to right
+            let pan = if voices == 1 {
+                0.0
+            } else {
+                2.0 * voice as f32 / (voices - 1) as f32 - 1.0
+            };
+            // each side of a voice reads its own channel, panning only weighs them
+            let gain = Stereo::panned(1.0, pan);
+            let delay = delay_ms * samples_per_ms;
+            wet += Stereo::new(gain.left * left.read(delay), gain.right * right.read(delay));
+        }
+        let wet = wet * (voices as f32).recip().sqrt();
+
+        self.phase = (self.phase + TAU * rate_hz / self.sample_rate).rem_euclid(TAU);
+        // the vibrato runs at a fixed multiple of the sweep, as on the classic ensembles
+        self.vibrato_phase =
+            (self.vibrato_phase + TAU * 7.3 * rate_hz / self.sample_rate).rem_euclid(TAU);
+
+        input * (1.0 - mix) + wet * mix
+    }
+}
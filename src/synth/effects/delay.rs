@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use crate::synth::effects::dsp::{DelayLine, OnePole, Smoothed};
+use crate::synth::effects::{DEFAULT_TEMPO_BPM, MIN_TEMPO_BPM};
+use crate::synth::stereo::Stereo;
+
+pub const MAX_DELAY_MS: f32 = 2000.0;
+pub const MAX_FEEDBACK: f32 = 0.95;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum NoteDivision {
+    Whole,
+    Half,
+    DottedHalf,
+    HalfTriplet,
+    Quarter,
+    DottedQuarter,
+    QuarterTriplet,
+    Eighth,
+    DottedEighth,
+    EighthTriplet,
+    Sixteenth,
+    DottedSixteenth,
+    SixteenthTriplet,
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    pub const ALL: [NoteDivision; 14] = [
+        NoteDivision::Whole,
+        NoteDivision::DottedHalf,
+        NoteDivision::Half,
+        NoteDivision::HalfTriplet,
+        NoteDivision::DottedQuarter,
+        NoteDivision::Quarter,
+        NoteDivision::QuarterTriplet,
+        NoteDivision::DottedEighth,
+        NoteDivision::Eighth,
+        NoteDivision::EighthTriplet,
+        NoteDivision::DottedSixteenth,
+        NoteDivision::Sixteenth,
+        NoteDivision::SixteenthTriplet,
+        NoteDivision::ThirtySecond,
+    ];
+
+    // length in quarter notes
+    pub fn beats(&self) -> f32 {
+        match self {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::DottedHalf => 3.0,
+            NoteDivision::HalfTriplet => 4.0 / 3.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::DottedQuarter => 1.5,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::DottedEighth => 0.75,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::DottedSixteenth => 0.375,
+            NoteDivision::SixteenthTriplet => 1.0 / 6.0,
+            NoteDivision::ThirtySecond => 0.125,
+        }
+    }
+}
+
+impl std::fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            NoteDivision::Whole => "1/1",
+            NoteDivision::Half => "1/2",
+            NoteDivision::DottedHalf => "1/2.",
+            NoteDivision::HalfTriplet => "1/2T",
+            NoteDivision::Quarter => "1/4",
+            NoteDivision::DottedQuarter => "1/4.",
+            NoteDivision::QuarterTriplet => "1/4T",
+            NoteDivision::Eighth => "1/8",
+            NoteDivision::DottedEighth => "1/8.",
+            NoteDivision::EighthTriplet => "1/8T",
+            NoteDivision::Sixteenth => "1/16",
+            NoteDivision::DottedSixteenth => "1/16.",
+            NoteDivision::SixteenthTriplet => "1/16T",
+            NoteDivision::ThirtySecond => "1/32",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DelayTime {
+    Ms(f32),
+    // follows the tempo
+    Sync(NoteDivision),
+}
+
+impl DelayTime {
+    pub fn ms(&self, tempo_bpm: f32) -> f32 {
+        match self {
+            DelayTime::Ms(ms) => *ms,
+            DelayTime::Sync(division) => division.beats() * 60_000.0 / tempo_bpm.max(1.0),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DelayParams {
+    pub time: DelayTime,
+    pub feedback: f32,
+    // cutoff of the lowpass in the feedback loop, each repeat gets darker
+    pub tone_hz: f32,
+    // the repeats alternate between left and right
+    pub ping_pong: bool,
+    pub mix: f32,
+}
+
+impl Default for DelayParams {
+    fn default() -> Self {
+        Self {
+            time: DelayTime::Sync(NoteDivision::DottedEighth),
+            feedback: 0.4,
+            tone_hz: 4000.0,
+            ping_pong: false,
+            mix: 0.0,
+        }
+    }
+}
+
+pub struct Delay {
+    params: DelayParams,
+    tempo_bpm: f32,
+    sample_rate: f32,
+    // in samples, glides when the time changes rather than jumping
+    delay: Smoothed,
+    lines: [DelayLine; 2],
+    tone: [OnePole; 2],
+}
+
+impl Delay {
+    pub fn new(sample_rate: f32) -> Self {
+        // long enough for the longest synced time, a whole note at the slowest tempo, which is
+        // well beyond `MAX_DELAY_MS`
+        let seconds = NoteDivision::Whole.beats() * 60.0 / MIN_TEMPO_BPM;
+        let len = (seconds * sample_rate) as usize + 2;
+        let params = DelayParams::default();
+        let mut delay = Self {
+            params,
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            sample_rate,
+            delay: Smoothed::new(1.0, 50.0, sample_rate),
+            lines: [DelayLine::new(len), DelayLine::new(len)],
+            tone: [OnePole::new(params.tone_hz, sample_rate); 2],
+        };
+        delay.update_time();
+        delay.delay.current = delay.delay.target;
+        delay
+    }
+
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.tempo_bpm = tempo_bpm;
+        self.update_time();
+    }
+
+    pub fn set_time(&mut self, time: DelayTime) {
+        self.params.time = time;
+        self.update_time();
+    }
+
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.params.feedback = feedback.clamp(0.0, MAX_FEEDBACK);
+    }
+
+    pub fn set_tone(&mut self, tone_hz: f32) {
+        self.params.tone_hz = tone_hz;
+        for filter in &mut self.tone {
+            filter.set_cutoff(tone_hz, self.sample_rate);
+        }
+    }
+
+    pub fn set_ping_pong(&mut self, ping_pong: bool) {
+        self.params.ping_pong = ping_pong;
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.params.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn update_time(&mut self) {
+        let samples = self.params.time.ms(self.tempo_bpm) * self.sample_rate / 1000.0;
+        self.delay.target = samples.clamp(1.0, (self.lines[0].len() - 1) as f32);
+    }
+
+    pub fn process(&mut self, input: Stereo) -> Stereo {
+        let delay = self.delay.next();
+        let [left, right] = &mut self.lines;
+        let wet = Stereo::new(left.read(delay), right.read(delay));
+        let feedback = Stereo::new(
+            self.tone[0].lowpass(wet.left),
+            self.tone[1].lowpass(wet.right),
+        ) * self.params.feedback;
+
+        if self.params.ping_pong {
+            // the input only enters on the left, the feedback crosses over on every repeat
+            left.push(input.mono() + feedback.right);
+            right.push(feedback.left);
+        } else {
+            left.push(input.left + feedback.left);
+            right.push(input.right + feedback.right);
+        }
+
+        input * (1.0 - self.params.mix) + wet * self.params.mix
+    }
+}
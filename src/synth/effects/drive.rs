@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+
+use crate::synth::effects::dsp::Oversampler;
+use crate::synth::stereo::Stereo;
+
+pub const MAX_DRIVE_DB: f32 = 36.0;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum DriveCurve {
+    #[default]
+    Tanh,
+    // a diode clips one half of the wave harder than the other, adding even harmonics
+    Diode,
+    // folds back from the rails instead of flattening, gets bright quickly
+    Foldback,
+}
+
+impl DriveCurve {
+    pub const ALL: [DriveCurve; 3] = [DriveCurve::Tanh, DriveCurve::Diode, DriveCurve::Foldback];
+
+    pub fn shape(&self, x: f32) -> f32 {
+        match self {
+            DriveCurve::Tanh => x.tanh(),
+            DriveCurve::Diode => {
+                if x >= 0.0 {
+                    1.0 - (-x).exp()
+                } else {
+                    -0.6 * (1.0 - (x / 0.6).exp())
+                }
+            }
+            DriveCurve::Foldback => {
+                let t = (x + 1.0).rem_euclid(4.0);
+                if t < 2.0 {
+                    t - 1.0
+                } else {
+                    3.0 - t
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DriveCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            DriveCurve::Tanh => "Tanh",
+            DriveCurve::Diode => "Diode",
+            DriveCurve::Foldback => "Foldback",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Oversampling {
+    Off,
+    X2,
+    #[default]
+    X4,
+    X8,
+}
+
+impl Oversampling {
+    pub const ALL: [Oversampling; 4] = [
+        Oversampling::Off,
+        Oversampling::X2,
+        Oversampling::X4,
+        Oversampling::X8,
+    ];
+
+    // in `ALL`
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn factor(&self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for Oversampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            Oversampling::Off => "Off",
+            Oversampling::X2 => "2x",
+            Oversampling::X4 => "4x",
+            Oversampling::X8 => "8x",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DriveParams {
+    pub enabled: bool,
+    pub curve: DriveCurve,
+    pub drive_db: f32,
+    // how much of the output goes back into the input, the Model D trick of patching
+    // the headphone jack into the external input, which feeds the mixer ahead of the filter
+    pub feedback: f32,
+    pub oversampling: Oversampling,
+}
+
+impl Default for DriveParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            curve: DriveCurve::default(),
+            drive_db: 12.0,
+            feedback: 0.0,
+            oversampling: Oversampling::default(),
+        }
+    }
+}
+
+// first in the chain, where the filter input would be
+pub struct Drive {
+    params: DriveParams,
+    gain: f32,
+    // a left and right pair for every setting, switching happens on the audio thread
+    oversamplers: [[Oversampler; 2]; Oversampling::ALL.len()],
+    // last output, fed back through the loop
+    previous: Stereo,
+}
+
+impl Default for Drive {
+    fn default() -> Self {
+        let params = DriveParams::default();
+        Self {
+            params,
+            gain: db_to_gain(params.drive_db),
+            oversamplers: Oversampling::ALL.map(|oversampling| {
+                let factor = oversampling.factor();
+                [Oversampler::new(factor), Oversampler::new(factor)]
+            }),
+            previous: Stereo::default(),
+        }
+    }
+}
+
+impl Drive {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.params.enabled = enabled;
+        self.previous = Stereo::default();
+    }
+
+    pub fn set_curve(&mut self, curve: DriveCurve) {
+        self.params.curve = curve;
+    }
+
+    pub fn set_drive(&mut self, drive_db: f32) {
+        self.params.drive_db = drive_db.clamp(0.0, MAX_DRIVE_DB);
+        self.gain = db_to_gain(self.params.drive_db);
+    }
+
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.params.feedback = feedback.clamp(0.0, 1.0);
+    }
+
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        if oversampling != self.params.oversampling {
+            // what the pair held from the last time it was used would click
+            self.oversamplers[oversampling.index()]
+                .iter_mut()
+                .for_each(Oversampler::clear);
+        }
+        self.params.oversampling = oversampling;
+    }
+
+    pub fn process(&mut self, input: Stereo) -> Stereo {
+        if !self.params.enabled {
+            return input;
+        }
+
+        let DriveParams {
+            curve, feedback, ..
+        } = self.params;
+        let gain = self.gain;
+        let input = input + self.previous * feedback;
+        let [left, right] = &mut self.oversamplers[self.params.oversampling.index()];
+        let output = Stereo::new(
+            left.process(input.left, |x| curve.shape(gain * x)),
+            right.process(input.right, |x| curve.shape(gain * x)),
+        );
+        self.previous = output;
+
+        output
+    }
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
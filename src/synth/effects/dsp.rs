@@ -0,0 +1,210 @@
+use std::f32::consts::TAU;
+
+use crate::math::{self, lerp};
+
+// building blocks shared by the effects
+
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    // the slot the next sample goes to
+    write: usize,
+}
+
+impl DelayLine {
+    pub fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(2)],
+            write: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        self.buffer[self.write] = sample;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+
+    // the sample pushed `delay` samples ago, linearly interpolated
+    // `delay` is clamped to [1, len - 1]
+    pub fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1.0, (len - 1) as f32);
+        let position = (self.write + len) as f32 - delay;
+        let index = position.floor() as usize;
+
+        lerp(
+            position.fract(),
+            self.buffer[index % len],
+            self.buffer[(index + 1) % len],
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+// one-pole lowpass, the highpass is what the lowpass leaves out
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OnePole {
+    state: f32,
+    coefficient: f32,
+}
+
+impl OnePole {
+    pub fn new(cutoff_hz: f32, sample_rate: f32) -> Self {
+        let mut filter = Self::default();
+        filter.set_cutoff(cutoff_hz, sample_rate);
+        filter
+    }
+
+    pub fn set_cutoff(&mut self, cutoff_hz: f32, sample_rate: f32) {
+        let cutoff_hz = cutoff_hz.clamp(1.0, 0.49 * sample_rate);
+        self.coefficient = 1.0 - (-TAU * cutoff_hz / sample_rate).exp();
+    }
+
+    pub fn lowpass(&mut self, input: f32) -> f32 {
+        self.state += self.coefficient * (input - self.state);
+        self.state
+    }
+
+    pub fn highpass(&mut self, input: f32) -> f32 {
+        input - self.lowpass(input)
+    }
+}
+
+// exponential approach to a target, against zipper noise when parameters jump
+#[derive(Copy, Clone, Debug)]
+pub struct Smoothed {
+    pub current: f32,
+    pub target: f32,
+    coefficient: f32,
+}
+
+impl Smoothed {
+    pub fn new(value: f32, time_ms: f32, sample_rate: f32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            coefficient: 1.0 - (-1000.0 / (time_ms * sample_rate)).exp(),
+        }
+    }
+
+    pub fn next(&mut self) -> f32 {
+        self.current += self.coefficient * (self.target - self.current);
+        self.current
+    }
+}
+
+// taps of each polyphase branch of the oversampling filters
+const OVERSAMPLING_TAPS: usize = 16;
+
+// runs a nonlinearity at `factor` times the sample rate
+// windowed-sinc FIRs for both the interpolation and the decimation
+pub struct Oversampler {
+    factor: usize,
+    kernel: Vec<f32>,
+    // input samples, for the polyphase interpolator
+    input: FirHistory,
+    // shaped samples at the higher rate, for the decimator
+    output: FirHistory,
+}
+
+impl Oversampler {
+    pub fn new(factor: usize) -> Self {
+        let factor = factor.max(1);
+        let len = OVERSAMPLING_TAPS * factor;
+        let center = (len - 1) as f32 / 2.0;
+        // a little below the original Nyquist frequency
+        let cutoff = 0.45 / factor as f32;
+        let mut kernel: Vec<f32> = (0..len)
+            .map(|i| {
+                let distance = i as f32 - center;
+                2.0 * cutoff
+                    * math::sinc(2.0 * cutoff * distance)
+                    * math::blackman(distance / (center + 1.0))
+            })
+            .collect();
+        let sum: f32 = kernel.iter().sum();
+        kernel.iter_mut().for_each(|tap| *tap /= sum);
+
+        Self {
+            factor,
+            kernel,
+            input: FirHistory::new(OVERSAMPLING_TAPS),
+            output: FirHistory::new(len),
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.output.clear();
+    }
+
+    pub fn process(&mut self, input: f32, mut shape: impl FnMut(f32) -> f32) -> f32 {
+        if self.factor == 1 {
+            return shape(input);
+        }
+
+        self.input.push(input);
+        for phase in 0..self.factor {
+            // the zero-stuffed samples only meet every `factor`th tap
+            let upsampled: f32 = self
+                .input
+                .latest()
+                .iter()
+                .rev()
+                .zip(self.kernel.iter().skip(phase).step_by(self.factor))
+                .map(|(sample, tap)| sample * tap)
+                .sum();
+            self.output.push(shape(self.factor as f32 * upsampled));
+        }
+
+        self.output
+            .latest()
+            .iter()
+            .rev()
+            .zip(&self.kernel)
+            .map(|(sample, tap)| sample * tap)
+            .sum()
+    }
+}
+
+// the last `len` samples, contiguous and oldest first
+struct FirHistory {
+    buffer: Vec<f32>,
+    len: usize,
+    position: usize,
+}
+
+impl FirHistory {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; 2 * len],
+            len,
+            position: 0,
+        }
+    }
+
+    // every sample is written twice so that `latest` never wraps around
+    fn push(&mut self, sample: f32) {
+        self.buffer[self.position] = sample;
+        self.buffer[self.position + self.len] = sample;
+        self.position = (self.position + 1) % self.len;
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    fn latest(&self) -> &[f32] {
+        &self.buffer[self.position..self.position + self.len]
+    }
+}
@@ -0,0 +1,48 @@
+pub mod chorus;
+pub mod delay;
+pub mod drive;
+mod dsp;
+pub mod reverb;
+
+use crate::synth::stereo::Stereo;
+
+pub use self::chorus::{Chorus, ChorusMode, ChorusParams};
+pub use self::delay::{Delay, DelayParams, DelayTime, NoteDivision};
+pub use self::drive::{Drive, DriveCurve, DriveParams, Oversampling};
+pub use self::reverb::{Reverb, ReverbParams};
+
+pub const DEFAULT_TEMPO_BPM: f32 = 120.0;
+pub const MIN_TEMPO_BPM: f32 = 20.0;
+pub const MAX_TEMPO_BPM: f32 = 300.0;
+
+// the chain between the voices and the output bus, in signal order
+pub struct Effects {
+    pub drive: Drive,
+    pub chorus: Chorus,
+    pub delay: Delay,
+    pub reverb: Reverb,
+}
+
+impl Effects {
+    // the buffers are allocated here, create it before handing it to the audio thread
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            drive: Drive::default(),
+            chorus: Chorus::new(sample_rate),
+            delay: Delay::new(sample_rate),
+            reverb: Reverb::new(sample_rate),
+        }
+    }
+
+    pub fn set_tempo(&mut self, tempo_bpm: f32) {
+        self.delay
+            .set_tempo(tempo_bpm.clamp(MIN_TEMPO_BPM, MAX_TEMPO_BPM));
+    }
+
+    pub fn process(&mut self, input: Stereo) -> Stereo {
+        let output = self.drive.process(input);
+        let output = self.chorus.process(output);
+        let output = self.delay.process(output);
+        self.reverb.process(output)
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use crate::synth::effects::dsp::{DelayLine, OnePole};
+use crate::synth::stereo::Stereo;
+
+pub const MAX_DECAY_S: f32 = 20.0;
+pub const MAX_PRE_DELAY_MS: f32 = 250.0;
+
+// feedback delay network: 8 lines mixed through a Hadamard matrix
+const LINES: usize = 8;
+// at full size, mutually prime-ish so that the echoes do not pile up
+const LINE_MS: [f32; LINES] = [29.7, 37.1, 41.1, 43.7, 53.3, 59.9, 67.7, 73.1];
+const MIN_SIZE: f32 = 0.1;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ReverbParams {
+    // scales the delay lines, 1 is a large hall
+    pub size: f32,
+    // RT60, the time it takes the tail to fall by 60 dB
+    pub decay_s: f32,
+    // 0 is bright, 1 loses the highs quickly
+    pub damping: f32,
+    pub pre_delay_ms: f32,
+    pub mix: f32,
+}
+
+impl Default for ReverbParams {
+    fn default() -> Self {
+        Self {
+            size: 0.6,
+            decay_s: 2.0,
+            damping: 0.4,
+            pre_delay_ms: 10.0,
+            mix: 0.0,
+        }
+    }
+}
+
+pub struct Reverb {
+    params: ReverbParams,
+    sample_rate: f32,
+    pre_delay: [DelayLine; 2],
+    lines: [DelayLine; LINES],
+    damping: [OnePole; LINES],
+    // per line, derived from size and decay
+    delays: [f32; LINES],
+    gains: [f32; LINES],
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f32) -> Self {
+        let samples = |ms: f32| (ms * sample_rate / 1000.0) as usize + 2;
+        let mut reverb = Self {
+            params: ReverbParams::default(),
+            sample_rate,
+            pre_delay: std::array::from_fn(|_| DelayLine::new(samples(MAX_PRE_DELAY_MS))),
+            lines: std::array::from_fn(|i| DelayLine::new(samples(LINE_MS[i]))),
+            damping: [OnePole::default(); LINES],
+            delays: [1.0; LINES],
+            gains: [0.0; LINES],
+        };
+        reverb.update();
+        reverb
+    }
+
+    pub fn set_size(&mut self, size: f32) {
+        self.params.size = size.clamp(MIN_SIZE, 1.0);
+        self.update();
+    }
+
+    pub fn set_decay(&mut self, decay_s: f32) {
+        self.params.decay_s = decay_s.clamp(0.1, MAX_DECAY_S);
+        self.update();
+    }
+
+    pub fn set_damping(&mut self, damping: f32) {
+        self.params.damping = damping.clamp(0.0, 1.0);
+        self.update();
+    }
+
+    pub fn set_pre_delay(&mut self, pre_delay_ms: f32) {
+        self.params.pre_delay_ms = pre_delay_ms.clamp(0.0, MAX_PRE_DELAY_MS);
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        // the network stops running at 0, do not replay what it held back then
+        if self.params.mix == 0.0 {
+            self.pre_delay.iter_mut().for_each(DelayLine::clear);
+            self.lines.iter_mut().for_each(DelayLine::clear);
+        }
+        self.params.mix = mix.clamp(0.0, 1.0);
+    }
+
+    fn update(&mut self) {
+        let cutoff_hz = 20000.0 * 0.05_f32.powf(self.params.damping);
+        let lines = self
+            .delays
+            .iter_mut()
+            .zip(&mut self.gains)
+            .zip(&mut self.damping);
+        for (((delay, gain), damping), line_ms) in lines.zip(LINE_MS) {
+            *delay = line_ms * self.params.size * self.sample_rate / 1000.0;
+            // -60 dB after decay_s seconds, spread over the passes through this line
+            let passes = self.params.decay_s * self.sample_rate / *delay;
+            *gain = 10.0_f32.powf(-3.0 / passes);
+            damping.set_cutoff(cutoff_hz, self.sample_rate);
+        }
+    }
+
+    pub fn process(&mut self, input: Stereo) -> Stereo {
+        if self.params.mix == 0.0 {
+            return input;
+        }
+
+        let pre_delay = self.params.pre_delay_ms * self.sample_rate / 1000.0;
+        let [pre_left, pre_right] = &mut self.pre_delay;
+        pre_left.push(input.left);
+        pre_right.push(input.right);
+        // a pre-delay of 0 still reads one sample back, which is inaudible
+        let dry = Stereo::new(pre_left.read(pre_delay), pre_right.read(pre_delay));
+
+        let mut outputs: [f32; LINES] =
+            std::array::from_fn(|i| self.damping[i].lowpass(self.lines[i].read(self.delays[i])));
+        let wet = Stereo::new(
+            outputs.iter().step_by(2).sum::<f32>(),
+            outputs.iter().skip(1).step_by(2).sum::<f32>(),
+        ) * (2.0 / LINES as f32);
+
+        hadamard(&mut outputs);
+        for (i, line) in self.lines.iter_mut().enumerate() {
+            // left feeds the even lines, right the odd ones
+            let input = if i % 2 == 0 { dry.left } else { dry.right };
+            line.push(input + self.gains[i] * outputs[i]);
+        }
+
+        input * (1.0 - self.params.mix) + wet * self.params.mix
+    }
+}
+
+// in place, orthonormal so that the network neither gains nor loses energy
+fn hadamard(data: &mut [f32; LINES]) {
+    let mut width = 1;
+    while width < LINES {
+        for start in (0..LINES).step_by(2 * width) {
+            for i in start..start + width {
+                let (a, b) = (data[i], data[i + width]);
+                data[i] = a + b;
+                data[i + width] = a - b;
+            }
+        }
+        width *= 2;
+    }
+    let norm = (LINES as f32).sqrt().recip();
+    for x in data {
+        *x *= norm;
+    }
+}
@@ -1,6 +1,35 @@
+pub mod effects;
+pub mod modulation;
+pub mod monitor;
+pub mod mts;
+pub mod output;
+pub mod scala;
+pub mod stereo;
+#[allow(clippy::module_inception)]
 pub mod synth;
 pub mod tuner;
+pub mod unison;
+pub mod voice;
 pub mod wavetable;
 
-pub use self::synth::{Envelope, Synth};
-pub use self::wavetable::{Wavetable, WavetableKind};
+pub use self::effects::chorus::{MAX_CHORUS_RATE_HZ, MAX_CHORUS_VOICES};
+pub use self::effects::delay::{MAX_DELAY_MS, MAX_FEEDBACK};
+pub use self::effects::drive::MAX_DRIVE_DB;
+pub use self::effects::reverb::{MAX_DECAY_S, MAX_PRE_DELAY_MS};
+pub use self::effects::{
+    ChorusMode, ChorusParams, DelayParams, DelayTime, DriveCurve, DriveParams, NoteDivision,
+    Oversampling, ReverbParams, DEFAULT_TEMPO_BPM, MAX_TEMPO_BPM, MIN_TEMPO_BPM,
+};
+pub use self::modulation::ModSource;
+pub use self::monitor::{OutputMonitor, VoiceMonitor, MONITOR_CAPACITY};
+pub use self::mts::{MtsMessage, MtsTable};
+pub use self::output::{OutputMeter, DEFAULT_CEILING_DB, MIN_CEILING_DB};
+pub use self::scala::{KeyboardMapping, Scale};
+pub use self::stereo::MAX_WIDTH;
+pub use self::synth::{
+    output_devices, Envelope, OfflineSynth, Synth, SynthError, WAVETABLE_DIR_ENV,
+};
+pub use self::tuner::Transpose;
+pub use self::unison::{UnisonPhase, MAX_DETUNE_CENTS, MAX_UNISON};
+pub use self::voice::{VoiceMode, VoiceState, VoiceStealing, MAX_VOICES};
+pub use self::wavetable::{Interpolation, Wavetable, WavetableBank, WavetableError, WavetableKind};
@@ -0,0 +1,52 @@
+use std::f32::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Lfo {
+    pub rate_hz: f32,
+    phase: f32,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self {
+            rate_hz: 1.0,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Lfo {
+    // bipolar sine, advances by one sample
+    pub fn next(&mut self, sample_rate: f32) -> f32 {
+        let value = self.phase.sin();
+        self.phase += TAU * self.rate_hz / sample_rate;
+        self.phase = self.phase.rem_euclid(TAU);
+
+        value
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ModSource {
+    #[default]
+    Off,
+    Envelope,
+    Lfo,
+}
+
+impl ModSource {
+    pub const ALL: [ModSource; 3] = [ModSource::Off, ModSource::Envelope, ModSource::Lfo];
+}
+
+impl std::fmt::Display for ModSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            ModSource::Off => "Off",
+            ModSource::Envelope => "Envelope",
+            ModSource::Lfo => "LFO",
+        };
+        write!(f, "{}", repr)
+    }
+}
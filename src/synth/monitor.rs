@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use crate::midi::MidiNote;
+use crate::synth::voice::{Voice, VoiceState, MAX_VOICES};
+
+// about 0.7 s at 48 kHz, enough for the largest analyzer window
+pub const MONITOR_CAPACITY: usize = 1 << 15;
+
+// the output as it goes to the device, for the scope and the analyzer
+// one writer, the audio thread, and any number of readers, without locks
+// a reader racing the writer may see a few samples of the next buffer, harmless for a display
+pub struct OutputMonitor {
+    // the bits of f32 samples, mono
+    samples: Box<[AtomicU32]>,
+    // total number of samples written, wraps around
+    written: AtomicUsize,
+}
+
+impl Default for OutputMonitor {
+    fn default() -> Self {
+        Self {
+            samples: (0..MONITOR_CAPACITY).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl OutputMonitor {
+    // `position` is the writer's own count, made visible to the readers by `publish`
+    pub fn store(&self, position: usize, sample: f32) {
+        self.samples[position % MONITOR_CAPACITY].store(sample.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn publish(&self, position: usize) {
+        self.written.store(position, Ordering::Release);
+    }
+
+    // fills `out` with the most recent samples, oldest first
+    // returns the total written so far, so that readers can tell whether anything changed
+    pub fn read_latest(&self, out: &mut [f32]) -> usize {
+        let written = self.written.load(Ordering::Acquire);
+        let len = out.len().min(MONITOR_CAPACITY);
+        let start = written.wrapping_sub(len);
+        for (i, sample) in out[..len].iter_mut().enumerate() {
+            let index = start.wrapping_add(i) % MONITOR_CAPACITY;
+            *sample = f32::from_bits(self.samples[index].load(Ordering::Relaxed));
+        }
+
+        written
+    }
+}
+
+// what each voice is doing, updated once per buffer by the audio thread
+pub struct VoiceMonitor {
+    // the state, note and volume of a voice packed together, see `pack`
+    voices: [AtomicU64; MAX_VOICES],
+}
+
+impl Default for VoiceMonitor {
+    fn default() -> Self {
+        Self {
+            voices: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl VoiceMonitor {
+    // voices past the end of `voices` are reported idle
+    pub fn store(&self, voices: &[Voice]) {
+        for (i, slot) in self.voices.iter().enumerate() {
+            let packed = voices
+                .get(i)
+                .map_or(0, |voice| pack(voice.state, voice.volume));
+            slot.store(packed, Ordering::Relaxed);
+        }
+    }
+
+    pub fn load(&self) -> [(VoiceState, f32); MAX_VOICES] {
+        std::array::from_fn(|i| unpack(self.voices[i].load(Ordering::Relaxed)))
+    }
+}
+
+// volume bits in the low half, then the note, then the stage
+fn pack(state: VoiceState, volume: f32) -> u64 {
+    let (stage, note) = match state {
+        VoiceState::Idle => (0, 0),
+        VoiceState::Attacking(note) => (1, note.note),
+        VoiceState::Decaying(note) => (2, note.note),
+        VoiceState::Sustaining(note) => (3, note.note),
+        VoiceState::Releasing(note) => (4, note.note),
+    };
+
+    (stage << 40) | ((note as u64) << 32) | volume.to_bits() as u64
+}
+
+fn unpack(packed: u64) -> (VoiceState, f32) {
+    let volume = f32::from_bits(packed as u32);
+    let note = MidiNote {
+        note: (packed >> 32) as u8,
+    };
+    let state = match packed >> 40 {
+        1 => VoiceState::Attacking(note),
+        2 => VoiceState::Decaying(note),
+        3 => VoiceState::Sustaining(note),
+        4 => VoiceState::Releasing(note),
+        _ => VoiceState::Idle,
+    };
+
+    (state, volume)
+}
@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+
+// MIDI Tuning Standard, see the MIDI 1.0 spec "MIDI Tuning Messages"
+// tuning programs and banks are ignored, every message retunes the current tuning
+const NON_REAL_TIME: u8 = 0x7E;
+const REAL_TIME: u8 = 0x7F;
+const MIDI_TUNING: u8 = 0x08;
+
+const BULK_DUMP: u8 = 0x01;
+const NOTE_CHANGE: u8 = 0x02;
+const NOTE_CHANGE_BANK: u8 = 0x07;
+const SCALE_OCTAVE_1_BYTE: u8 = 0x08;
+const SCALE_OCTAVE_2_BYTE: u8 = 0x09;
+
+// a frequency data triplet, the nearest 12-TET note below and a 14-bit fraction of a semitone
+// `7F 7F 7F` means "no change"
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoteTuning {
+    Unchanged,
+    Semitones(f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MtsMessage {
+    BulkDump {
+        name: String,
+        notes: Vec<NoteTuning>,
+    },
+    NoteChange(Vec<(u8, NoteTuning)>),
+    // cents offset of each pitch class from 12-TET, starting at C
+    ScaleOctave([f32; 12]),
+}
+
+impl MtsMessage {
+    // `sysex` includes the leading `F0`, the trailing `F7` is optional
+    pub fn parse(sysex: &[u8]) -> Result<Self, MtsError> {
+        let body = sysex.strip_prefix(&[0xF0]).ok_or(MtsError::NotSysex)?;
+        let body = body.strip_suffix(&[0xF7]).unwrap_or(body);
+
+        let [realtime, _device, MIDI_TUNING, sub_id, data @ ..] = body else {
+            return Err(MtsError::NotMts);
+        };
+        if *realtime != NON_REAL_TIME && *realtime != REAL_TIME {
+            return Err(MtsError::NotMts);
+        }
+
+        match *sub_id {
+            // program, 16 bytes of name, 128 triplets, checksum
+            BULK_DUMP => {
+                let data = data.get(1..1 + 16 + 3 * 128).ok_or(MtsError::Truncated)?;
+                let name = String::from_utf8_lossy(&data[..16]).trim_end().to_string();
+                let notes = data[16..].chunks_exact(3).map(note_tuning).collect();
+                Ok(MtsMessage::BulkDump { name, notes })
+            }
+            // [bank], program, count, count * (key, triplet)
+            NOTE_CHANGE | NOTE_CHANGE_BANK => {
+                let data = if *sub_id == NOTE_CHANGE_BANK {
+                    data.get(1..).ok_or(MtsError::Truncated)?
+                } else {
+                    data
+                };
+                let count = *data.get(1).ok_or(MtsError::Truncated)? as usize;
+                let changes = data
+                    .get(2..2 + 4 * count)
+                    .ok_or(MtsError::Truncated)?
+                    .chunks_exact(4)
+                    .map(|change| (change[0] & 0x7F, note_tuning(&change[1..])))
+                    .collect();
+                Ok(MtsMessage::NoteChange(changes))
+            }
+            // 3 bytes of channel mask, 12 offsets
+            SCALE_OCTAVE_1_BYTE => {
+                let data = data.get(3..3 + 12).ok_or(MtsError::Truncated)?;
+                let cents = std::array::from_fn(|i| data[i] as f32 - 64.0);
+                Ok(MtsMessage::ScaleOctave(cents))
+            }
+            SCALE_OCTAVE_2_BYTE => {
+                let data = data.get(3..3 + 24).ok_or(MtsError::Truncated)?;
+                let cents = std::array::from_fn(|i| {
+                    let value =
+                        ((data[2 * i] as u16 & 0x7F) << 7) | (data[2 * i + 1] as u16 & 0x7F);
+                    (value as f32 - 8192.0) * 100.0 / 8192.0
+                });
+                Ok(MtsMessage::ScaleOctave(cents))
+            }
+            other => Err(MtsError::Unsupported(other)),
+        }
+    }
+}
+
+fn note_tuning(triplet: &[u8]) -> NoteTuning {
+    if triplet == [0x7F, 0x7F, 0x7F] {
+        return NoteTuning::Unchanged;
+    }
+    let fraction = ((triplet[1] as u16 & 0x7F) << 7) | (triplet[2] as u16 & 0x7F);
+
+    NoteTuning::Semitones((triplet[0] & 0x7F) as f32 + fraction as f32 / 16384.0)
+}
+
+// the retuned notes, as MIDI note numbers on a 12-TET A4 = 440 Hz scale
+// notes that were never retuned keep following the Scala tuning
+// fixed-size so that it goes to the audio thread whole, without anything to free there
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SavedMtsTable", into = "SavedMtsTable")]
+pub struct MtsTable {
+    notes: [Option<f32>; 128],
+}
+
+impl Default for MtsTable {
+    fn default() -> Self {
+        Self { notes: [None; 128] }
+    }
+}
+
+impl MtsTable {
+    // the notes a message retunes, the others are `None`
+    pub fn changes(message: &MtsMessage) -> Self {
+        let mut changes = Self::default();
+        match message {
+            MtsMessage::BulkDump { notes, .. } => {
+                for (note, tuning) in notes.iter().enumerate().take(128) {
+                    if let NoteTuning::Semitones(semitones) = tuning {
+                        changes.notes[note] = Some(*semitones);
+                    }
+                }
+            }
+            MtsMessage::NoteChange(notes) => {
+                for (note, tuning) in notes {
+                    if let NoteTuning::Semitones(semitones) = tuning {
+                        changes.notes[*note as usize & 0x7F] = Some(*semitones);
+                    }
+                }
+            }
+            MtsMessage::ScaleOctave(cents) => {
+                for (note, semitones) in changes.notes.iter_mut().enumerate() {
+                    *semitones = Some(note as f32 + cents[note % 12] / 100.0);
+                }
+            }
+        }
+
+        changes
+    }
+
+    pub fn apply(&mut self, message: &MtsMessage) {
+        self.merge(&Self::changes(message));
+    }
+
+    // the notes retuned in `changes` replace ours
+    pub fn merge(&mut self, changes: &MtsTable) {
+        for (note, change) in self.notes.iter_mut().zip(&changes.notes) {
+            if change.is_some() {
+                *note = *change;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.iter().all(Option::is_none)
+    }
+
+    pub fn frequency(&self, note: u8) -> Option<f32> {
+        let semitones = self.notes.get(note as usize).copied().flatten()?;
+
+        Some(440.0 * 2.0_f32.powf((semitones - 69.0) / 12.0))
+    }
+}
+
+// as patches store it, an empty list until the first retuning
+#[derive(Serialize, Deserialize)]
+struct SavedMtsTable {
+    notes: Vec<Option<f32>>,
+}
+
+impl From<SavedMtsTable> for MtsTable {
+    fn from(saved: SavedMtsTable) -> Self {
+        let mut table = Self::default();
+        for (note, semitones) in table.notes.iter_mut().zip(saved.notes) {
+            *note = semitones;
+        }
+        table
+    }
+}
+
+impl From<MtsTable> for SavedMtsTable {
+    fn from(table: MtsTable) -> Self {
+        let notes = if table.is_empty() {
+            Vec::new()
+        } else {
+            table.notes.to_vec()
+        };
+        Self { notes }
+    }
+}
+
+#[derive(Debug)]
+pub enum MtsError {
+    NotSysex,
+    NotMts,
+    Truncated,
+    Unsupported(u8),
+}
+
+impl std::fmt::Display for MtsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MtsError::NotSysex => write!(f, "not a SysEx message"),
+            MtsError::NotMts => write!(f, "not a MIDI Tuning Standard message"),
+            MtsError::Truncated => write!(f, "truncated MIDI Tuning Standard message"),
+            MtsError::Unsupported(sub_id) => {
+                write!(
+                    f,
+                    "unsupported MIDI Tuning Standard message {:#04x}",
+                    sub_id
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MtsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sysex(realtime: u8, sub_id: u8, data: &[u8]) -> Vec<u8> {
+        let mut sysex = vec![0xF0, realtime, 0x7F, MIDI_TUNING, sub_id];
+        sysex.extend_from_slice(data);
+        sysex.push(0xF7);
+        sysex
+    }
+
+    // program 0, a name and one triplet per note, the checksum is not checked
+    fn bulk_dump(triplets: impl Fn(u8) -> [u8; 3]) -> Vec<u8> {
+        let mut data = vec![0];
+        data.extend_from_slice(b"Test tuning     ");
+        for note in 0..128 {
+            data.extend_from_slice(&triplets(note));
+        }
+        data.push(0);
+        sysex(NON_REAL_TIME, BULK_DUMP, &data)
+    }
+
+    fn assert_hz(table: &MtsTable, note: u8, expected: f32) {
+        let frequency = table.frequency(note).unwrap();
+        let cents = 1200.0 * (frequency / expected).log2();
+        assert!(
+            cents.abs() < 0.01,
+            "note {note}: {frequency} Hz, expected {expected} Hz"
+        );
+    }
+
+    #[test]
+    fn bulk_dump_with_unchanged_notes() {
+        // even notes a quarter tone up, odd ones unchanged
+        let message = MtsMessage::parse(&bulk_dump(|note| {
+            if note % 2 == 0 {
+                [note, 0x40, 0x00]
+            } else {
+                [0x7F, 0x7F, 0x7F]
+            }
+        }))
+        .unwrap();
+        let MtsMessage::BulkDump { name, notes } = &message else {
+            panic!("{message:?}");
+        };
+        assert_eq!(name, "Test tuning");
+        assert_eq!(notes.len(), 128);
+        assert_eq!(notes[60], NoteTuning::Semitones(60.5));
+        assert_eq!(notes[61], NoteTuning::Unchanged);
+
+        let mut table = MtsTable::default();
+        table.apply(&MtsMessage::NoteChange(vec![(
+            61,
+            NoteTuning::Semitones(61.0),
+        )]));
+        table.apply(&message);
+        assert_hz(&table, 60, 440.0 * 2.0_f32.powf((60.5 - 69.0) / 12.0));
+        assert_hz(&table, 61, 440.0 * 2.0_f32.powf((61.0 - 69.0) / 12.0));
+        assert_eq!(table.frequency(63), None);
+    }
+
+    #[test]
+    fn single_note_changes() {
+        // A4 to 440 Hz and C4 to the 12-TET E4, the 14-bit fraction is 1/4 of a semitone
+        let changes = [2, 69, 69, 0x00, 0x00, 60, 64, 0x20, 0x00];
+        let expected = vec![
+            (69, NoteTuning::Semitones(69.0)),
+            (60, NoteTuning::Semitones(64.25)),
+        ];
+
+        // program, count, changes
+        let without_bank: Vec<u8> = [&[0][..], &changes].concat();
+        // bank, program, count, changes
+        let with_bank: Vec<u8> = [&[1, 0][..], &changes].concat();
+        for realtime in [REAL_TIME, NON_REAL_TIME] {
+            for (sub_id, data) in [(NOTE_CHANGE, &without_bank), (NOTE_CHANGE_BANK, &with_bank)] {
+                let message = MtsMessage::parse(&sysex(realtime, sub_id, data)).unwrap();
+                assert_eq!(message, MtsMessage::NoteChange(expected.clone()));
+            }
+        }
+
+        let mut table = MtsTable::default();
+        table.apply(&MtsMessage::parse(&sysex(REAL_TIME, NOTE_CHANGE, &without_bank)).unwrap());
+        assert_hz(&table, 69, 440.0);
+        assert_hz(&table, 60, 440.0 * 2.0_f32.powf((64.25 - 69.0) / 12.0));
+        assert_eq!(table.frequency(61), None);
+    }
+
+    #[test]
+    fn scale_octave_messages() {
+        // C down 64 cents, A in tune, B up 63 cents
+        let mut offsets = [64; 12];
+        offsets[0] = 0;
+        offsets[11] = 127;
+        let one_byte = [&[0x03, 0x7F, 0x7F][..], &offsets].concat();
+        let MtsMessage::ScaleOctave(cents) =
+            MtsMessage::parse(&sysex(NON_REAL_TIME, SCALE_OCTAVE_1_BYTE, &one_byte)).unwrap()
+        else {
+            panic!();
+        };
+        assert_eq!(cents[0], -64.0);
+        assert_eq!(cents[9], 0.0);
+        assert_eq!(cents[11], 63.0);
+
+        // C down 100 cents, A in tune, B up 50 cents
+        let mut offsets = [0x40, 0x00].repeat(12);
+        offsets[0..2].copy_from_slice(&[0x00, 0x00]);
+        offsets[22..24].copy_from_slice(&[0x60, 0x00]);
+        let two_byte = [&[0x03, 0x7F, 0x7F][..], &offsets].concat();
+        let message = MtsMessage::parse(&sysex(REAL_TIME, SCALE_OCTAVE_2_BYTE, &two_byte)).unwrap();
+        let MtsMessage::ScaleOctave(cents) = message else {
+            panic!("{message:?}");
+        };
+        assert_eq!(cents[0], -100.0);
+        assert_eq!(cents[9], 0.0);
+        assert_eq!(cents[11], 50.0);
+
+        // every octave
+        let mut table = MtsTable::default();
+        table.apply(&message);
+        assert_hz(&table, 69, 440.0);
+        assert_hz(&table, 57, 220.0);
+        assert_hz(&table, 60, 440.0 * 2.0_f32.powf(-10.0 / 12.0));
+        assert_hz(&table, 83, 440.0 * 2.0_f32.powf(14.5 / 12.0));
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let bulk = bulk_dump(|note| [note, 0, 0]);
+        let note_change = sysex(REAL_TIME, NOTE_CHANGE, &[0, 2, 69, 69, 0, 0]);
+        let scale_octave = sysex(
+            NON_REAL_TIME,
+            SCALE_OCTAVE_2_BYTE,
+            &[0x03, 0x7F, 0x7F, 0x40],
+        );
+        for sysex in [&bulk[..bulk.len() - 10], &note_change, &scale_octave] {
+            assert!(
+                matches!(MtsMessage::parse(sysex), Err(MtsError::Truncated)),
+                "{sysex:02x?}"
+            );
+        }
+        assert!(matches!(
+            MtsMessage::parse(&[0xF0, 0x7E, 0x7F, MIDI_TUNING]),
+            Err(MtsError::NotMts)
+        ));
+        assert!(matches!(
+            MtsMessage::parse(&[0x90, 60, 100]),
+            Err(MtsError::NotSysex)
+        ));
+    }
+
+    #[test]
+    fn tables_are_saved_as_lists() {
+        let table = MtsTable::default();
+        assert_eq!(serde_json::to_string(&table).unwrap(), r#"{"notes":[]}"#);
+
+        let mut table = MtsTable::default();
+        table.apply(&MtsMessage::NoteChange(vec![(
+            60,
+            NoteTuning::Semitones(60.5),
+        )]));
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(serde_json::from_str::<MtsTable>(&json).unwrap(), table);
+    }
+}
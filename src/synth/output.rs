@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::synth::stereo::Stereo;
+
+pub const MIN_CEILING_DB: f32 = -24.0;
+pub const DEFAULT_CEILING_DB: f32 = -1.0;
+const LOOKAHEAD_MS: f32 = 5.0;
+const RELEASE_MS: f32 = 80.0;
+const DC_CUTOFF_HZ: f32 = 10.0;
+
+// written by the audio thread, read and cleared by the GUI
+#[derive(Debug, Default)]
+pub struct OutputMeter {
+    // the bits of an f32, the highest level that reached the limiter since the last read
+    peak: AtomicU32,
+    clipped: AtomicBool,
+}
+
+impl OutputMeter {
+    fn record(&self, peak: f32, clipped: bool) {
+        if peak > f32::from_bits(self.peak.load(Ordering::Relaxed)) {
+            self.peak.store(peak.to_bits(), Ordering::Relaxed);
+        }
+        if clipped {
+            self.clipped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // peak level and whether the limiter had to step in since the last call
+    pub fn take(&self) -> (f32, bool) {
+        let peak = f32::from_bits(self.peak.swap(0, Ordering::Relaxed));
+        let clipped = self.clipped.swap(false, Ordering::Relaxed);
+        (peak, clipped)
+    }
+}
+
+// one-pole high-pass, removes the offset of asymmetric shapers and wavetables
+#[derive(Copy, Clone, Debug, Default)]
+struct DcBlocker {
+    coefficient: f32,
+    input: Stereo,
+    output: Stereo,
+}
+
+impl DcBlocker {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            coefficient: 1.0 - std::f32::consts::TAU * DC_CUTOFF_HZ / sample_rate,
+            ..Default::default()
+        }
+    }
+
+    fn process(&mut self, input: Stereo) -> Stereo {
+        self.output = input - self.input + self.output * self.coefficient;
+        self.input = input;
+        self.output
+    }
+}
+
+// stereo-linked lookahead limiter, no sample leaves above the ceiling
+// the gain needed for each peak is held for the lookahead, released exponentially
+// and averaged over the lookahead, so that it has ramped down when the peak comes out
+struct Limiter {
+    ceiling: f32,
+    release: f32,
+    lookahead: usize,
+    delay: VecDeque<Stereo>,
+    // increasing gains and the sample they were computed for, for the sliding minimum
+    minimum: VecDeque<(u64, f32)>,
+    // the smoothed gains being averaged, and their sum
+    average: VecDeque<f32>,
+    sum: f64,
+    gain: f32,
+    clock: u64,
+}
+
+impl Limiter {
+    fn new(sample_rate: f32) -> Self {
+        let lookahead = ((LOOKAHEAD_MS * sample_rate / 1000.0) as usize).max(1);
+        // `process` pushes before it pops, one more than they hold leaves them room to grow
+        // without reallocating on the audio thread
+        let mut delay = VecDeque::with_capacity(lookahead + 1);
+        delay.extend(std::iter::repeat_n(Stereo::default(), lookahead - 1));
+        let mut average = VecDeque::with_capacity(lookahead + 1);
+        average.extend(std::iter::repeat_n(1.0, lookahead));
+        Self {
+            ceiling: 1.0,
+            release: 1.0 - (-1000.0 / (RELEASE_MS * sample_rate)).exp(),
+            lookahead,
+            delay,
+            minimum: VecDeque::with_capacity(lookahead + 1),
+            average,
+            sum: lookahead as f64,
+            gain: 1.0,
+            clock: 0,
+        }
+    }
+
+    fn process(&mut self, input: Stereo) -> Stereo {
+        let peak = f32::max(input.left.abs(), input.right.abs());
+        let target = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+
+        self.clock += 1;
+        while self.minimum.back().is_some_and(|(_, gain)| *gain >= target) {
+            self.minimum.pop_back();
+        }
+        self.minimum.push_back((self.clock, target));
+        while self
+            .minimum
+            .front()
+            .is_some_and(|(clock, _)| *clock + self.lookahead as u64 <= self.clock)
+        {
+            self.minimum.pop_front();
+        }
+        let held = self.minimum.front().map_or(1.0, |(_, gain)| *gain);
+
+        // instant attack, the averaging below is what smooths it
+        if held < self.gain {
+            self.gain = held;
+        } else {
+            self.gain += self.release * (held - self.gain);
+        }
+
+        self.average.push_back(self.gain);
+        self.sum += self.gain as f64 - self.average.pop_front().unwrap_or(1.0) as f64;
+        let gain = (self.sum / self.lookahead as f64) as f32;
+
+        self.delay.push_back(input);
+        let delayed = self.delay.pop_front().unwrap_or_default() * gain.min(1.0);
+        // rounding in the running sum must not let anything through
+        Stereo::new(
+            delayed.left.clamp(-self.ceiling, self.ceiling),
+            delayed.right.clamp(-self.ceiling, self.ceiling),
+        )
+    }
+}
+
+// the last thing before the device
+pub struct OutputStage {
+    dc_blocker: DcBlocker,
+    limiter: Limiter,
+    // since the last report
+    peak: f32,
+    clipped: bool,
+}
+
+impl OutputStage {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut stage = Self {
+            dc_blocker: DcBlocker::new(sample_rate),
+            limiter: Limiter::new(sample_rate),
+            peak: 0.0,
+            clipped: false,
+        };
+        stage.set_ceiling(DEFAULT_CEILING_DB);
+        stage
+    }
+
+    pub fn set_ceiling(&mut self, ceiling_db: f32) {
+        self.limiter.ceiling = 10.0_f32.powf(ceiling_db.clamp(MIN_CEILING_DB, 0.0) / 20.0);
+    }
+
+    pub fn process(&mut self, input: Stereo) -> Stereo {
+        // a NaN would otherwise stick in the filters for good
+        let input = if input.left.is_finite() && input.right.is_finite() {
+            input
+        } else {
+            Stereo::default()
+        };
+        let input = self.dc_blocker.process(input);
+        let peak = f32::max(input.left.abs(), input.right.abs());
+        self.peak = self.peak.max(peak);
+        self.clipped |= peak > self.limiter.ceiling;
+
+        self.limiter.process(input)
+    }
+
+    // once per buffer rather than per sample
+    pub fn report(&mut self, meter: &OutputMeter) {
+        meter.record(self.peak, self.clipped);
+        self.peak = 0.0;
+        self.clipped = false;
+    }
+}
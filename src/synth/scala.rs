@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+// a Scala scale, see https://www.huygens-fokker.org/scala/scl_format.html
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scale {
+    pub description: String,
+    // degrees 1..=n in cents, the last one is the period (usually 1200)
+    cents: Vec<f64>,
+}
+
+impl Scale {
+    pub fn equal_temperament(notes: usize) -> Self {
+        let cents = (1..=notes)
+            .map(|degree| 1200.0 * degree as f64 / notes as f64)
+            .collect();
+
+        Self {
+            description: format!("{notes}-TET"),
+            cents,
+        }
+    }
+
+    pub fn from_disk(path: impl AsRef<Path>) -> Result<Self, ScalaError> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(ScalaError::Io)
+            .and_then(|text| Self::parse(&text))
+            .map_err(|err| ScalaError::InFile(path.to_path_buf(), Box::new(err)))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        // the description is the first line that is not a comment, it can be empty
+        let mut lines = text.lines().filter(|line| !line.starts_with('!'));
+        let description = lines
+            .next()
+            .ok_or(ScalaError::MissingField("description"))?
+            .trim()
+            .to_string();
+        let notes: usize = first_token(lines.next().ok_or(ScalaError::MissingField("note count"))?)
+            .parse()
+            .map_err(|_| ScalaError::MissingField("note count"))?;
+
+        let cents: Vec<f64> = lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take(notes)
+            .map(parse_pitch)
+            .collect::<Result<_, _>>()?;
+        if cents.len() != notes {
+            return Err(ScalaError::NoteCount {
+                expected: notes,
+                found: cents.len(),
+            });
+        }
+        if notes == 0 {
+            return Err(ScalaError::MissingField("pitches"));
+        }
+
+        Ok(Self { description, cents })
+    }
+
+    pub fn len(&self) -> usize {
+        self.cents.len()
+    }
+
+    // parsing rejects scales without notes, this is for the type's sake
+    pub fn is_empty(&self) -> bool {
+        self.cents.is_empty()
+    }
+
+    pub fn period(&self) -> f64 {
+        self.cents.last().copied().unwrap_or(1200.0)
+    }
+
+    // any integer degree, 0 being the unison
+    // an empty scale, which `parse` never returns, behaves like 12-TET
+    pub fn cents(&self, degree: i32) -> f64 {
+        if self.cents.is_empty() {
+            return 100.0 * degree as f64;
+        }
+        let n = self.len() as i32;
+        let periods = degree.div_euclid(n);
+        let index = degree.rem_euclid(n);
+        let within_period = if index == 0 {
+            0.0
+        } else {
+            self.cents[index as usize - 1]
+        };
+
+        periods as f64 * self.period() + within_period
+    }
+}
+
+// a Scala keyboard mapping, see https://www.huygens-fokker.org/scala/help.htm#mappings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyboardMapping {
+    pub first_note: u8,
+    pub last_note: u8,
+    // the note where scale degree 0 is mapped
+    pub middle_note: u8,
+    pub reference_note: u8,
+    pub reference_frequency: f64,
+    // scale degree spanned by one repetition of `keys`, 0 means the scale's period
+    pub octave_degree: i32,
+    // scale degree for each key of the pattern, `None` for unmapped keys
+    // empty for the linear mapping
+    pub keys: Vec<Option<i32>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            keys: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn from_disk(path: impl AsRef<Path>) -> Result<Self, ScalaError> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(ScalaError::Io)
+            .and_then(|text| Self::parse(&text))
+            .map_err(|err| ScalaError::InFile(path.to_path_buf(), Box::new(err)))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut tokens = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('!') && !line.is_empty())
+            .map(first_token);
+        let mut field = |name: &'static str| -> Result<&str, ScalaError> {
+            tokens.next().ok_or(ScalaError::MissingField(name))
+        };
+        fn number<T: std::str::FromStr>(token: &str, name: &'static str) -> Result<T, ScalaError> {
+            token
+                .parse()
+                .map_err(|_| ScalaError::InvalidNumber(name, token.to_string()))
+        }
+
+        let map_size: usize = number(field("map size")?, "map size")?;
+        let first_note: u8 = number(field("first note")?, "first note")?;
+        let last_note: u8 = number(field("last note")?, "last note")?;
+        let middle_note: u8 = number(field("middle note")?, "middle note")?;
+        let reference_note: u8 = number(field("reference note")?, "reference note")?;
+        let reference_frequency: f64 =
+            number(field("reference frequency")?, "reference frequency")?;
+        let octave_degree: i32 = number(field("octave degree")?, "octave degree")?;
+        // trailing unlisted keys are unmapped
+        let keys: Vec<Option<i32>> = (0..map_size)
+            .map(|_| match field("key") {
+                Ok("x") | Ok("X") | Err(_) => Ok(None),
+                Ok(token) => number(token, "key").map(Some),
+            })
+            .collect::<Result<_, _>>()?;
+
+        if reference_frequency <= 0.0 || !reference_frequency.is_finite() {
+            return Err(ScalaError::InvalidNumber(
+                "reference frequency",
+                reference_frequency.to_string(),
+            ));
+        }
+
+        Ok(Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            keys,
+        })
+    }
+
+    // the scale degree played by `note`, `None` if unmapped
+    pub fn degree(&self, note: u8, scale: &Scale) -> Option<i32> {
+        if note < self.first_note || note > self.last_note {
+            return None;
+        }
+        let offset = note as i32 - self.middle_note as i32;
+        if self.keys.is_empty() {
+            return Some(offset);
+        }
+
+        let size = self.keys.len() as i32;
+        let octave_degree = if self.octave_degree == 0 {
+            scale.len() as i32
+        } else {
+            self.octave_degree
+        };
+        let key = self.keys[offset.rem_euclid(size) as usize]?;
+
+        Some(offset.div_euclid(size) * octave_degree + key)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScalaError {
+    Io(std::io::Error),
+    MissingField(&'static str),
+    InvalidNumber(&'static str, String),
+    InvalidPitch(String),
+    NoteCount { expected: usize, found: usize },
+    InFile(PathBuf, Box<ScalaError>),
+}
+
+impl std::fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScalaError::Io(err) => write!(f, "{}", err),
+            ScalaError::MissingField(name) => write!(f, "missing {}", name),
+            ScalaError::InvalidNumber(name, token) => write!(f, "invalid {}: {:?}", name, token),
+            ScalaError::InvalidPitch(token) => write!(f, "invalid pitch: {:?}", token),
+            ScalaError::NoteCount { expected, found } => {
+                write!(f, "expected {} pitches, found {}", expected, found)
+            }
+            ScalaError::InFile(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for ScalaError {}
+
+fn first_token(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+// cents if there is a period, a ratio (`3/2`) or an integer (`2`) otherwise
+fn parse_pitch(line: &str) -> Result<f64, ScalaError> {
+    let token = first_token(line);
+    let invalid = || ScalaError::InvalidPitch(token.to_string());
+
+    if token.contains('.') {
+        return token.parse().map_err(|_| invalid());
+    }
+    let (numerator, denominator) = token.split_once('/').unwrap_or((token, "1"));
+    let numerator: f64 = numerator.parse().map_err(|_| invalid())?;
+    let denominator: f64 = denominator.parse().map_err(|_| invalid())?;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return Err(invalid());
+    }
+
+    Ok(1200.0 * (numerator / denominator).log2())
+}
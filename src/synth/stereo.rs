@@ -0,0 +1,100 @@
+use std::f32::consts::FRAC_PI_4;
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+pub const MAX_WIDTH: f32 = 2.0;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Stereo {
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Stereo {
+    pub fn new(left: f32, right: f32) -> Self {
+        Self { left, right }
+    }
+
+    // equal-power pan law, `pan` goes from -1 (left) to 1 (right)
+    pub fn panned(sample: f32, pan: f32) -> Self {
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+        Self::new(sample * angle.cos(), sample * angle.sin())
+    }
+
+    pub fn mono(&self) -> f32 {
+        0.5 * (self.left + self.right)
+    }
+
+    // writes one interleaved frame, extra channels are left silent
+    pub fn write_frame(&self, frame: &mut [f32]) {
+        match frame {
+            [] => {}
+            [mono] => *mono = self.mono(),
+            [left, right, rest @ ..] => {
+                *left = self.left;
+                *right = self.right;
+                rest.fill(cpal::Sample::EQUILIBRIUM);
+            }
+        }
+    }
+}
+
+impl Add for Stereo {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.left + rhs.left, self.right + rhs.right)
+    }
+}
+
+impl Sub for Stereo {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.left - rhs.left, self.right - rhs.right)
+    }
+}
+
+impl AddAssign for Stereo {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Mul<f32> for Stereo {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self::new(self.left * rhs, self.right * rhs)
+    }
+}
+
+// the internal stereo bus between the voices and the device
+#[derive(Copy, Clone, Debug)]
+pub struct Bus {
+    // balance, from -1 (left) to 1 (right)
+    pub pan: f32,
+    // 0 is mono, 1 leaves the image untouched, up to 2 widens it
+    pub width: f32,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self {
+            pan: 0.0,
+            width: 1.0,
+        }
+    }
+}
+
+impl Bus {
+    pub fn process(&self, input: Stereo) -> Stereo {
+        let mid = 0.5 * (input.left + input.right);
+        let side = 0.5 * (input.left - input.right) * self.width;
+        let pan = self.pan.clamp(-1.0, 1.0);
+
+        Stereo::new(
+            (mid + side) * f32::min(1.0 - pan, 1.0),
+            (mid - side) * f32::min(1.0 + pan, 1.0),
+        )
+    }
+}
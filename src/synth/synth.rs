@@ -1,15 +1,28 @@
 use std::f32::consts::PI;
+use std::path::PathBuf;
 use std::sync::{mpsc, Arc};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::Stream;
+use serde::{Deserialize, Serialize};
 
 use crate::event::Event;
 use crate::midi::MidiNote;
+use crate::synth::effects::Effects;
+use crate::synth::modulation::{Lfo, ModSource};
+use crate::synth::monitor::{OutputMonitor, VoiceMonitor};
+use crate::synth::output::{OutputMeter, OutputStage};
+use crate::synth::scala::{KeyboardMapping, Scale};
+use crate::synth::stereo::{Bus, Stereo};
 use crate::synth::tuner::Tuner;
-use crate::synth::wavetable::{Wavetable, WavetableBank, WavetableKind};
+use crate::synth::unison::{Unison, MAX_UNISON};
+use crate::synth::voice::{VoiceAllocator, MAX_VOICES};
+use crate::synth::wavetable::{Interpolation, Wavetable, WavetableBank, WavetableKind};
 
-#[derive(Copy, Clone, Debug)]
+// a directory whose files override the embedded wavetables of the same name
+pub const WAVETABLE_DIR_ENV: &str = "MODELP_WAVETABLE_DIR";
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Envelope {
     pub attack_ms: u16,
     pub decay_ms: u16,
@@ -17,6 +30,12 @@ pub struct Envelope {
     pub release_ms: u16,
 }
 
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope::new(5, 100, 0.7, 150)
+    }
+}
+
 impl Envelope {
     fn new(attack_ms: u16, decay_ms: u16, sustain: f32, release_ms: u16) -> Self {
         Self {
@@ -27,204 +46,481 @@ impl Envelope {
         }
     }
 
-    pub fn default() -> Self {
-        Envelope::new(5, 100, 0.7, 150)
-    }
-
-    fn attack_increment(&self, sample_rate: f32) -> f32 {
+    pub fn attack_increment(&self, sample_rate: f32) -> f32 {
         1000.0 / (sample_rate * self.attack_ms as f32)
     }
 
-    fn decay_increment(&self, sample_rate: f32) -> f32 {
+    pub fn decay_increment(&self, sample_rate: f32) -> f32 {
         1000.0 * (1.0 - self.sustain) / (sample_rate * self.decay_ms as f32)
     }
 
-    fn release_decrement(&self, sample_rate: f32) -> f32 {
+    pub fn release_decrement(&self, sample_rate: f32) -> f32 {
         1000.0 / (sample_rate * self.release_ms as f32)
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-enum VoiceState {
-    Idle,
-    Attacking(MidiNote),
-    Decaying(MidiNote),
-    Sustaining(MidiNote),
-    Releasing(MidiNote),
-}
-
-impl VoiceState {
-    fn get_note(&self) -> Option<MidiNote> {
-        match self {
-            VoiceState::Idle => None,
-            VoiceState::Attacking(note) => Some(*note),
-            VoiceState::Decaying(note) => Some(*note),
-            VoiceState::Sustaining(note) => Some(*note),
-            VoiceState::Releasing(note) => Some(*note),
-        }
-    }
-}
-
 struct AudioThreadState {
-    voice_state: VoiceState,
+    voices: VoiceAllocator,
+    unison: Unison,
+    envelope: Envelope,
+    tuner: Tuner,
     wavetable_bank: Arc<WavetableBank>,
     wavetable_kind: WavetableKind,
+    interpolation: Interpolation,
+    oscillator_pan: f32,
+    position: f32,
+    position_mod_source: ModSource,
+    position_mod_amount: f32,
+    lfo: Lfo,
     message_rx: mpsc::Receiver<Event>,
-    volume: f32,
+    effects: Effects,
     master: f32,
-    phase: f32,
+    bus: Bus,
+    output: OutputStage,
+    meter: Arc<OutputMeter>,
+    monitor: Arc<OutputMonitor>,
+    monitor_position: usize,
+    voice_monitor: Arc<VoiceMonitor>,
+    sample_rate: f32,
+    channels: usize,
     update_period: usize,
     update_timer: usize,
 }
 
 impl AudioThreadState {
-    fn set_state(&mut self, voice_state: VoiceState) {
-        dbg!(&voice_state);
-        self.voice_state = voice_state;
+    fn new(
+        sample_rate: f32,
+        channels: usize,
+        message_rx: mpsc::Receiver<Event>,
+        wavetable_bank: Arc<WavetableBank>,
+    ) -> Self {
+        Self {
+            voices: VoiceAllocator::default(),
+            unison: Unison::default(),
+            envelope: Envelope::default(),
+            tuner: Tuner::default(),
+            wavetable_bank,
+            wavetable_kind: WavetableKind::Triangle,
+            interpolation: Interpolation::default(),
+            oscillator_pan: 0.0,
+            position: 0.0,
+            position_mod_source: ModSource::Off,
+            position_mod_amount: 0.0,
+            lfo: Lfo::default(),
+            message_rx,
+            effects: Effects::new(sample_rate),
+            master: 0.7,
+            bus: Bus::default(),
+            output: OutputStage::new(sample_rate),
+            meter: Arc::new(OutputMeter::default()),
+            monitor: Arc::new(OutputMonitor::default()),
+            monitor_position: 0,
+            voice_monitor: Arc::new(VoiceMonitor::default()),
+            sample_rate,
+            channels,
+            update_period: 5,
+            update_timer: 0,
+        }
+    }
+
+    fn handle_events(&mut self) {
+        while let Ok(event) = self.message_rx.try_recv() {
+            self.handle_event(event);
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::NoteOn(incoming_note, velocity) => {
+                let start_phases = self.unison.start_phases();
+                self.voices.note_on(incoming_note, velocity, start_phases)
+            }
+            Event::NoteOff(incoming_note) => self.voices.note_off(incoming_note),
+            Event::SetVoiceMode(mode) => self.voices.set_mode(mode),
+            Event::SetPolyphony(polyphony) => self.voices.set_polyphony(polyphony),
+            Event::SetVoiceStealing(stealing) => self.voices.set_stealing(stealing),
+            Event::SetUnisonVoices(voices) => self.unison.set_voices(voices),
+            Event::SetUnisonDetune(cents) => self.unison.set_detune(cents),
+            Event::SetUnisonSpread(spread) => self.unison.set_spread(spread),
+            Event::SetUnisonPhase(phase) => self.unison.set_phase(phase),
+            Event::SetTranspose(transpose) => self.tuner.set_transpose(transpose),
+            Event::SetScale(scale) => self.tuner.set_scale(scale),
+            Event::SetKeyboardMapping(mapping) => self.tuner.set_mapping(mapping),
+            Event::SetTuningReference(note, frequency) => self.tuner.set_reference(note, frequency),
+            Event::Retune(changes) => self.tuner.retune(&changes),
+            Event::SetRetuning(table) => self.tuner.set_mts(table),
+            Event::ChangeOscillator(osc) => self.wavetable_kind = osc,
+            // the GUI still holds the old bank, it is not freed here
+            Event::SetWavetableBank(bank) => self.wavetable_bank = bank,
+            Event::SetInterpolation(interpolation) => self.interpolation = interpolation,
+            Event::SetOscillatorPan(pan) => self.oscillator_pan = pan,
+            Event::SetPosition(position) => self.position = position,
+            Event::SetPositionModSource(source) => self.position_mod_source = source,
+            Event::SetPositionModAmount(amount) => self.position_mod_amount = amount,
+            Event::SetLfoRate(rate_hz) => self.lfo.rate_hz = rate_hz,
+            Event::SetTempo(tempo_bpm) => self.effects.set_tempo(tempo_bpm),
+            Event::SetDriveEnabled(enabled) => self.effects.drive.set_enabled(enabled),
+            Event::SetDriveCurve(curve) => self.effects.drive.set_curve(curve),
+            Event::SetDriveGain(drive_db) => self.effects.drive.set_drive(drive_db),
+            Event::SetDriveFeedback(feedback) => self.effects.drive.set_feedback(feedback),
+            Event::SetDriveOversampling(oversampling) => {
+                self.effects.drive.set_oversampling(oversampling)
+            }
+            Event::SetChorusMode(mode) => self.effects.chorus.set_mode(mode),
+            Event::SetChorusRate(rate_hz) => self.effects.chorus.set_rate(rate_hz),
+            Event::SetChorusDepth(depth) => self.effects.chorus.set_depth(depth),
+            Event::SetChorusVoices(voices) => self.effects.chorus.set_voices(voices),
+            Event::SetChorusMix(mix) => self.effects.chorus.set_mix(mix),
+            Event::SetDelayTime(time) => self.effects.delay.set_time(time),
+            Event::SetDelayFeedback(feedback) => self.effects.delay.set_feedback(feedback),
+            Event::SetDelayTone(tone_hz) => self.effects.delay.set_tone(tone_hz),
+            Event::SetDelayPingPong(ping_pong) => self.effects.delay.set_ping_pong(ping_pong),
+            Event::SetDelayMix(mix) => self.effects.delay.set_mix(mix),
+            Event::SetReverbSize(size) => self.effects.reverb.set_size(size),
+            Event::SetReverbDecay(decay_s) => self.effects.reverb.set_decay(decay_s),
+            Event::SetReverbDamping(damping) => self.effects.reverb.set_damping(damping),
+            Event::SetReverbPreDelay(ms) => self.effects.reverb.set_pre_delay(ms),
+            Event::SetReverbMix(mix) => self.effects.reverb.set_mix(mix),
+            Event::SetMaster(master) => self.master = master,
+            Event::SetPan(pan) => self.bus.pan = pan,
+            Event::SetWidth(width) => self.bus.width = width,
+            Event::SetCeiling(ceiling_db) => self.output.set_ceiling(ceiling_db),
+            Event::SetAttackMs(ms) => self.envelope.attack_ms = ms,
+            Event::SetDecayMs(ms) => self.envelope.decay_ms = ms,
+            Event::SetSustain(sustain) => self.envelope.sustain = sustain,
+            Event::SetReleaseMs(ms) => self.envelope.release_ms = ms,
+        }
+    }
+
+    fn render(&mut self, data: &mut [f32]) {
+        // no early return when the voices are idle, the effects still have tails to play
+        let sample_rate = self.sample_rate;
+        let wavetable = self.wavetable_bank.get(self.wavetable_kind);
+        // unmapped notes are silent
+        let mut frequencies: [Option<f32>; MAX_VOICES] = [None; MAX_VOICES];
+        for (frequency, voice) in frequencies.iter_mut().zip(self.voices.voices()) {
+            *frequency = voice.state.get_note().and_then(|note| self.tuner.get(note));
+        }
+
+        let unison = self.unison;
+        let copies = unison.voices();
+        let ratios: [f32; MAX_UNISON] = std::array::from_fn(|i| unison.frequency_ratio(i));
+        // the unison copies are spread around the oscillator's own position
+        let pans: [f32; MAX_UNISON] =
+            std::array::from_fn(|i| (self.oscillator_pan + unison.pan(i)).clamp(-1.0, 1.0));
+
+        for frame in data.chunks_mut(self.channels) {
+            let lfo = self.lfo.next(sample_rate);
+            let mut new_sample = Stereo::default();
+            for (voice, frequency) in self.voices.voices_mut().iter_mut().zip(frequencies) {
+                let Some(frequency) = frequency else {
+                    continue;
+                };
+                let modulation = match self.position_mod_source {
+                    ModSource::Off => 0.0,
+                    ModSource::Envelope => voice.volume,
+                    ModSource::Lfo => lfo,
+                };
+                let position = self.position + self.position_mod_amount * modulation;
+                let copies = voice.phases.iter_mut().zip(ratios).zip(pans).take(copies);
+                for ((phase, ratio), pan) in copies {
+                    let frequency = frequency * ratio;
+                    let sample =
+                        wavetable.at(*phase, position, frequency, sample_rate, self.interpolation);
+                    new_sample += Stereo::panned(voice.volume * voice.velocity * sample, pan);
+                    *phase += 2.0 * PI * frequency / sample_rate;
+                    *phase = phase.rem_euclid(2.0 * PI);
+                }
+            }
+            let new_sample = self.effects.process(new_sample * unison.gain());
+            let new_sample = self.bus.process(new_sample) * self.master;
+            let new_sample = self.output.process(new_sample);
+            new_sample.write_frame(frame);
+            self.monitor.store(self.monitor_position, new_sample.mono());
+            self.monitor_position = self.monitor_position.wrapping_add(1);
+
+            if self.update_timer.is_multiple_of(self.update_period) {
+                for voice in self.voices.voices_mut() {
+                    voice.update_envelope(&self.envelope, sample_rate, self.update_period);
+                }
+                if self.update_timer == 20 * self.update_period {
+                    self.update_timer = 0;
+                } else {
+                    self.update_timer += 1;
+                }
+            } else {
+                self.update_timer += 1;
+            }
+        }
+        self.output.report(&self.meter);
+        self.monitor.publish(self.monitor_position);
+        self.voice_monitor.store(self.voices.voices());
     }
 }
 
 pub struct Synth {
     message_tx: mpsc::Sender<Event>,
+    meter: Arc<OutputMeter>,
+    monitor: Arc<OutputMonitor>,
+    voice_monitor: Arc<VoiceMonitor>,
+    sample_rate: f32,
     _stream: Stream,
+    wavetables: Wavetables,
+    tunings: SentTunings,
+}
+
+// the GUI side of the wavetable bank, a copy of the audio thread's plus the one it replaced
+// the replaced bank is kept alive until the next import, by then the audio thread has
+// dropped its reference and the memory is freed here rather than in the callback
+struct Wavetables {
+    bank: Arc<WavetableBank>,
+    previous: Option<Arc<WavetableBank>>,
+}
+
+impl Wavetables {
+    fn load() -> Self {
+        let wavetable_dir: Option<PathBuf> = std::env::var_os(WAVETABLE_DIR_ENV).map(PathBuf::from);
+        let bank = WavetableBank::load(wavetable_dir.as_deref()).unwrap_or_else(|err| {
+            eprintln!("failed to load the wavetables ({err}), using the embedded ones");
+            WavetableBank::new()
+        });
+        Self {
+            bank: Arc::new(bank),
+            previous: None,
+        }
+    }
+
+    fn add(&mut self, wavetable: Wavetable, message_tx: &mpsc::Sender<Event>) -> WavetableKind {
+        let mut bank = WavetableBank::clone(&self.bank);
+        let kind = bank.add(Arc::new(wavetable));
+        let bank = Arc::new(bank);
+        let _ = message_tx.send(Event::SetWavetableBank(bank.clone()));
+        self.previous = Some(std::mem::replace(&mut self.bank, bank));
+
+        kind
+    }
+}
+
+// the scales and mappings sent to the audio thread, held here until it has let go of them so
+// that they are freed here rather than in the callback
+struct SentTunings {
+    scales: Vec<Arc<Scale>>,
+    mappings: Vec<Arc<KeyboardMapping>>,
+}
+
+impl SentTunings {
+    // with the ones the audio thread starts with
+    fn new(tuner: &Tuner) -> Self {
+        Self {
+            scales: vec![tuner.scale().clone()],
+            mappings: vec![tuner.mapping().clone()],
+        }
+    }
+
+    fn keep(&mut self, event: &Event) {
+        // only ours is left once the audio thread has replaced it
+        self.scales.retain(|scale| Arc::strong_count(scale) > 1);
+        self.mappings
+            .retain(|mapping| Arc::strong_count(mapping) > 1);
+        match event {
+            Event::SetScale(scale) => self.scales.push(scale.clone()),
+            Event::SetKeyboardMapping(mapping) => self.mappings.push(mapping.clone()),
+            _ => {}
+        }
+    }
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Synth {
+    // the GUI has nothing to show without audio
     pub fn new() -> Self {
+        Self::with_output_device(None).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // `None` for the default device, see `output_devices` for the names
+    pub fn with_output_device(name: Option<&str>) -> Result<Self, SynthError> {
         let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .expect("no output device available");
+        let device = match name {
+            Some(name) => host
+                .output_devices()
+                .map_err(SynthError::Devices)?
+                .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+                .ok_or_else(|| SynthError::NoDevice(Some(name.to_string())))?,
+            None => host
+                .default_output_device()
+                .ok_or(SynthError::NoDevice(None))?,
+        };
 
-        let mut supported_configs_range = device
+        // a stereo config if there is one, mono devices get a downmix of the bus
+        let supported_configs: Vec<_> = device
             .supported_output_configs()
-            .expect("error while querying configs");
-        let stream_config = supported_configs_range
-            .next()
-            .expect("no supported config?!")
+            .map_err(SynthError::Configs)?
+            .collect();
+        let stream_config = supported_configs
+            .iter()
+            .find(|config| config.channels() == 2)
+            .or(supported_configs.first())
+            .ok_or(SynthError::NoConfig)?
             .with_max_sample_rate();
         let sample_rate: f32 = stream_config.sample_rate().0 as f32;
 
         let (message_tx, message_rx) = mpsc::channel::<Event>();
-
+        let wavetables = Wavetables::load();
         // vvv moved into thread
-        let mut envelope = Envelope::default();
-        let mut tuner = Tuner::default();
-        let mut state = AudioThreadState {
-            voice_state: VoiceState::Idle,
-            wavetable_bank: Arc::new(WavetableBank::new()),
-            wavetable_kind: WavetableKind::Triangle,
+        let mut state = AudioThreadState::new(
+            sample_rate,
+            stream_config.channels() as usize,
             message_rx,
-            volume: 0.0,
-            master: 0.7,
-            phase: 0.0,
-            update_period: 5,
-            update_timer: 0,
-        };
-        dbg!(&envelope);
+            wavetables.bank.clone(),
+        );
+        let meter = state.meter.clone();
+        let monitor = state.monitor.clone();
+        let voice_monitor = state.voice_monitor.clone();
+        let tunings = SentTunings::new(&state.tuner);
 
         let callback = move |data: &mut [f32], _info: &cpal::OutputCallbackInfo| {
-            'message_loop: loop {
-                let event = state.message_rx.try_recv();
-                if event.is_err() {
-                    break 'message_loop;
-                }
-                dbg!(&event.unwrap());
-
-                match event.unwrap() {
-                    Event::NoteOn(incoming_note) => {
-                        state.set_state(VoiceState::Attacking(incoming_note));
-                    }
-                    Event::NoteOff(incoming_note) => {
-                        let current_note = state.voice_state.get_note();
-                        if current_note.is_some() && current_note.unwrap() != incoming_note {
-                            continue 'message_loop;
-                        }
-                        state.set_state(VoiceState::Releasing(incoming_note));
-                    }
-                    Event::OctaveUp => tuner.octave_up(),
-                    Event::OctaveDown => tuner.octave_down(),
-                    Event::ChangeOscillator(osc) => state.wavetable_kind = osc,
-                    Event::SetMaster(master) => state.master = master,
-                    Event::SetAttackMs(ms) => envelope.attack_ms = ms,
-                    Event::SetDecayMs(ms) => envelope.decay_ms = ms,
-                    Event::SetSustain(sustain) => envelope.sustain = sustain,
-                    Event::SetReleaseMs(ms) => envelope.release_ms = ms,
-                }
-            }
-            if state.voice_state == VoiceState::Idle {
-                for sample in data {
-                    *sample = cpal::Sample::EQUILIBRIUM;
-                }
-                state.volume = 0.0;
-                return;
-            }
-            let frequency: f32 = tuner.get(state.voice_state.get_note().unwrap());
-            for sample in data {
-                let new_sample = state.master
-                    * state.volume
-                    * state
-                        .wavetable_bank
-                        .get(state.wavetable_kind)
-                        .at(state.phase);
-                *sample = new_sample;
-                state.phase += 2.0 * PI * frequency / sample_rate;
-                state.phase = state.phase.rem_euclid(2.0 * PI);
-
-                if state.update_timer % state.update_period == 0 {
-                    if let VoiceState::Attacking(note) = state.voice_state {
-                        if state.volume >= 1.0 {
-                            state.volume = 1.0;
-                            state.set_state(VoiceState::Decaying(note));
-                        } else {
-                            state.volume +=
-                                state.update_period as f32 * envelope.attack_increment(sample_rate);
-                            state.volume = f32::min(state.volume, 1.0);
-                        }
-                    } else if let VoiceState::Decaying(note) = state.voice_state {
-                        if state.volume <= envelope.sustain {
-                            state.volume = envelope.sustain;
-                            state.set_state(VoiceState::Sustaining(note));
-                        } else {
-                            state.volume -=
-                                state.update_period as f32 * envelope.decay_increment(sample_rate);
-                            state.volume = f32::max(state.volume, envelope.sustain);
-                        }
-                    } else if let VoiceState::Releasing(_) = state.voice_state {
-                        if state.volume <= 0.0 {
-                            state.volume = 0.0;
-                            state.set_state(VoiceState::Idle);
-                        } else {
-                            state.volume -= state.update_period as f32
-                                * envelope.release_decrement(sample_rate);
-                            state.volume = f32::max(state.volume, 0.0);
-                        }
-                    }
-                    if state.update_timer == 20 * state.update_period {
-                        state.update_timer = 0;
-                        //dbg!(state.volume);
-                    } else {
-                        state.update_timer += 1;
-                    }
-                } else {
-                    state.update_timer += 1;
-                }
-            }
+            state.handle_events();
+            state.render(data);
         };
 
         let err_fn = |err| eprintln!("an error occurred on the output audio stream: {}", err);
         let _stream = device
             .build_output_stream(&stream_config.config(), callback, err_fn, None)
-            .expect("failed to open output stream");
+            .map_err(SynthError::Stream)?;
         let _ = _stream.play();
 
-        Self {
+        Ok(Self {
             message_tx,
+            meter,
+            monitor,
+            voice_monitor,
+            sample_rate,
             _stream,
+            wavetables,
+            tunings,
+        })
+    }
+
+    // the kind that selects the new table with `Event::ChangeOscillator`
+    pub fn add_wavetable(&mut self, wavetable: Wavetable) -> WavetableKind {
+        self.wavetables.add(wavetable, &self.message_tx)
+    }
+
+    pub fn meter(&self) -> &OutputMeter {
+        &self.meter
+    }
+
+    pub fn monitor(&self) -> &OutputMonitor {
+        &self.monitor
+    }
+
+    pub fn voice_monitor(&self) -> &VoiceMonitor {
+        &self.voice_monitor
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    // for sources that run on their own thread, e.g. MIDI input
+    pub fn event_sender(&self) -> mpsc::Sender<Event> {
+        self.message_tx.clone()
+    }
+
+    pub fn send_event(&mut self, event: Event) {
+        self.tunings.keep(&event);
+        let _ = self.message_tx.send(event);
+    }
+}
+
+// the same engine without an audio device, the caller pulls the samples
+// for bouncing to a file
+pub struct OfflineSynth {
+    message_tx: mpsc::Sender<Event>,
+    state: AudioThreadState,
+    wavetables: Wavetables,
+}
+
+impl OfflineSynth {
+    pub const CHANNELS: usize = 2;
+
+    pub fn new(sample_rate: f32) -> Self {
+        let (message_tx, message_rx) = mpsc::channel::<Event>();
+        let wavetables = Wavetables::load();
+        Self {
+            message_tx,
+            state: AudioThreadState::new(
+                sample_rate,
+                Self::CHANNELS,
+                message_rx,
+                wavetables.bank.clone(),
+            ),
+            wavetables,
         }
     }
 
+    pub fn add_wavetable(&mut self, wavetable: Wavetable) -> WavetableKind {
+        self.wavetables.add(wavetable, &self.message_tx)
+    }
+
+    // handled before the next call to `render`
     pub fn send_event(&mut self, event: Event) {
         let _ = self.message_tx.send(event);
     }
+
+    // `data` is interleaved stereo
+    pub fn render(&mut self, data: &mut [f32]) {
+        self.state.handle_events();
+        self.state.render(data);
+    }
+}
+
+// the names `Synth::with_output_device` takes, and whether each is the default
+pub fn output_devices() -> Vec<(String, bool)> {
+    let host = cpal::default_host();
+    let default = host
+        .default_output_device()
+        .and_then(|device| device.name().ok());
+    let Ok(devices) = host.output_devices() else {
+        return Vec::new();
+    };
+    devices
+        .filter_map(|device| device.name().ok())
+        .map(|name| {
+            let is_default = default.as_ref() == Some(&name);
+            (name, is_default)
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum SynthError {
+    Devices(cpal::DevicesError),
+    // the name that was asked for, `None` for the default device
+    NoDevice(Option<String>),
+    Configs(cpal::SupportedStreamConfigsError),
+    NoConfig,
+    Stream(cpal::BuildStreamError),
 }
+
+impl std::fmt::Display for SynthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SynthError::Devices(err) => write!(f, "can't list the audio outputs: {}", err),
+            SynthError::NoDevice(Some(name)) => write!(f, "no audio output named {:?}", name),
+            SynthError::NoDevice(None) => write!(f, "no audio output available"),
+            SynthError::Configs(err) => {
+                write!(f, "can't query the audio output's configs: {}", err)
+            }
+            SynthError::NoConfig => write!(f, "the audio output has no supported config"),
+            SynthError::Stream(err) => write!(f, "can't open the audio output: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SynthError {}
@@ -1,28 +1,370 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
 use crate::midi::MidiNote;
+use crate::synth::mts::MtsTable;
+use crate::synth::scala::{KeyboardMapping, Scale};
 
-#[derive(Debug)]
+// 12-TET with A4 = 440 Hz unless a Scala scale and keyboard mapping are loaded
+// notes retuned through MTS take precedence over the Scala tuning
+#[derive(Clone, Debug)]
 pub struct Tuner {
-    a4: f32,
+    scale: Arc<Scale>,
+    mapping: Arc<KeyboardMapping>,
+    // the mapping's until `set_reference`, the shared mapping is not written to
+    reference_note: u8,
+    reference_frequency: f64,
+    mts: MtsTable,
+    transpose: Transpose,
 }
 
 impl Default for Tuner {
     fn default() -> Self {
-        Self { a4: 440.0 }
+        let mapping = KeyboardMapping::default();
+        Self {
+            scale: Arc::new(Scale::equal_temperament(12)),
+            reference_note: mapping.reference_note,
+            reference_frequency: mapping.reference_frequency,
+            mapping: Arc::new(mapping),
+            mts: MtsTable::default(),
+            transpose: Transpose::default(),
+        }
     }
 }
 
 impl Tuner {
-    pub fn get(&self, note: MidiNote) -> f32 {
-        let offset_from_a4: i16 = note.note as i16 - 69;
+    // `None` if the note is not mapped to any scale degree
+    pub fn get(&self, note: MidiNote) -> Option<f32> {
+        if let Some(frequency) = self.mts.frequency(note.note) {
+            return Some(frequency * self.transpose.ratio() as f32);
+        }
+
+        let degree = self.mapping.degree(note.note, &self.scale)?;
+        // an unmapped reference note falls back to the middle note
+        let reference_degree = self
+            .mapping
+            .degree(self.reference_note, &self.scale)
+            .unwrap_or(0);
+        let cents = self.scale.cents(degree) - self.scale.cents(reference_degree);
+        let frequency =
+            self.reference_frequency * 2.0_f64.powf(cents / 1200.0) * self.transpose.ratio();
+
+        Some(frequency as f32)
+    }
+
+    pub fn scale(&self) -> &Arc<Scale> {
+        &self.scale
+    }
+
+    pub fn mapping(&self) -> &Arc<KeyboardMapping> {
+        &self.mapping
+    }
+
+    pub fn set_scale(&mut self, scale: Arc<Scale>) {
+        self.scale = scale;
+    }
+
+    pub fn set_mapping(&mut self, mapping: Arc<KeyboardMapping>) {
+        self.reference_note = mapping.reference_note;
+        self.reference_frequency = mapping.reference_frequency;
+        self.mapping = mapping;
+    }
+
+    pub fn set_reference(&mut self, note: MidiNote, frequency: f32) {
+        self.reference_note = note.note;
+        self.reference_frequency = frequency as f64;
+    }
+
+    pub fn retune(&mut self, changes: &MtsTable) {
+        self.mts.merge(changes);
+    }
+
+    pub fn set_mts(&mut self, table: MtsTable) {
+        self.mts = table;
+    }
+
+    pub fn set_transpose(&mut self, transpose: Transpose) {
+        self.transpose = transpose.clamped();
+    }
+}
+
+// applied on top of the tuning, whatever its source
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transpose {
+    pub octaves: i8,
+    pub semitones: i8,
+    // master tune
+    pub cents: f32,
+}
+
+impl Transpose {
+    pub const MAX_OCTAVES: i8 = 3;
+    pub const MAX_SEMITONES: i8 = 12;
+    pub const MAX_CENTS: f32 = 100.0;
 
-        self.a4 * 2.0_f32.powf(offset_from_a4 as f32 / 12.0)
+    pub fn clamped(&self) -> Self {
+        Self {
+            octaves: self.octaves.clamp(-Self::MAX_OCTAVES, Self::MAX_OCTAVES),
+            semitones: self
+                .semitones
+                .clamp(-Self::MAX_SEMITONES, Self::MAX_SEMITONES),
+            cents: self.cents.clamp(-Self::MAX_CENTS, Self::MAX_CENTS),
+        }
     }
 
     pub fn octave_up(&mut self) {
-        self.a4 *= 2.0;
+        self.octaves = (self.octaves + 1).min(Self::MAX_OCTAVES);
     }
 
     pub fn octave_down(&mut self) {
-        self.a4 /= 2.0;
+        self.octaves = (self.octaves - 1).max(-Self::MAX_OCTAVES);
+    }
+
+    pub fn ratio(&self) -> f64 {
+        let semitones = 12.0 * self.octaves as f64 + self.semitones as f64;
+
+        2.0_f64.powf(semitones / 12.0 + self.cents as f64 / 1200.0)
+    }
+}
+
+impl std::fmt::Display for Transpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:+} oct {:+} st {:+.1} ct",
+            self.octaves, self.semitones, self.cents
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::scala::ScalaError;
+
+    // from the Scala archive, 5-limit just intonation with one pitch given in cents
+    const JUST_12: &str = "\
+! ji_12.scl
+!
+5-limit 12-tone just intonation
+ 12
+!
+ 16/15
+ 9/8
+ 6/5
+ 5/4
+ 4/3
+ 45/32
+ 701.955001
+ 8/5
+ 5/3
+ 9/5
+ 15/8
+ 2/1
+";
+
+    // a just major scale on the white keys, the black keys are unmapped
+    const JUST_MAJOR: &str = "\
+! ji_major.scl
+Just major scale
+7
+9/8
+5/4
+4/3
+3/2
+5/3
+15/8
+2/1
+";
+
+    const WHITE_KEYS: &str = "\
+! white.kbm
+! Size of map:
+12
+! First MIDI note number to retune:
+0
+! Last MIDI note number to retune:
+127
+! Middle note where the first entry of the mapping is mapped to:
+60
+! Reference note for which frequency is given:
+69
+! Frequency to tune the above note to
+440.0
+! Scale degree to consider as formal octave:
+7
+! Mapping.
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+";
+
+    // the Scala archive's default mapping, C4 is degree 0 and A4 is 440 Hz
+    const A440: &str = "\
+! a440.kbm
+0
+0
+127
+60
+69
+440.0
+0
+";
+
+    const MAX_ERROR_CENTS: f64 = 0.01;
+
+    fn assert_tuning(tuner: &Tuner, expected: &[(u8, Option<f64>)]) {
+        for &(note, frequency) in expected {
+            let actual = tuner.get(MidiNote { note });
+            match (actual, frequency) {
+                (Some(actual), Some(frequency)) => {
+                    let error = 1200.0 * (actual as f64 / frequency).log2();
+                    assert!(
+                        error.abs() < MAX_ERROR_CENTS,
+                        "note {note}: {actual} Hz, expected {frequency} Hz ({error:+.4} cents)"
+                    );
+                }
+                _ => assert_eq!(actual.is_some(), frequency.is_some(), "note {note}"),
+            }
+        }
+    }
+
+    #[test]
+    fn just_intonation_with_a_linear_mapping() {
+        let scale = Scale::parse(JUST_12).unwrap();
+        assert_eq!(scale.description, "5-limit 12-tone just intonation");
+        assert_eq!(scale.len(), 12);
+        assert_eq!(scale.period(), 1200.0);
+
+        let mut tuner = Tuner::default();
+        tuner.set_scale(Arc::new(scale));
+        tuner.set_mapping(Arc::new(KeyboardMapping::parse(A440).unwrap()));
+
+        // A4 is the reference, degree 9 (5/3) above C4
+        let c4 = 440.0 * 3.0 / 5.0;
+        assert_tuning(
+            &tuner,
+            &[
+                (60, Some(c4)),
+                (61, Some(c4 * 16.0 / 15.0)),
+                (64, Some(c4 * 5.0 / 4.0)),
+                (67, Some(c4 * 3.0 / 2.0)),
+                (69, Some(440.0)),
+                (71, Some(c4 * 15.0 / 8.0)),
+                (72, Some(c4 * 2.0)),
+                (55, Some(c4 * 3.0 / 4.0)),
+                (48, Some(c4 / 2.0)),
+                (0, Some(c4 / 32.0)),
+                (127, Some(c4 * 32.0 * 3.0 / 2.0)),
+            ],
+        );
+    }
+
+    #[test]
+    fn mapping_with_unmapped_keys() {
+        let scale = Scale::parse(JUST_MAJOR).unwrap();
+        let mapping = KeyboardMapping::parse(WHITE_KEYS).unwrap();
+        assert_eq!(mapping.octave_degree, 7);
+        assert_eq!(
+            mapping.keys,
+            [
+                Some(0),
+                None,
+                Some(1),
+                None,
+                Some(2),
+                Some(3),
+                None,
+                Some(4),
+                None,
+                Some(5),
+                None,
+                Some(6)
+            ]
+        );
+        assert_eq!(mapping.degree(69, &scale), Some(5));
+        assert_eq!(mapping.degree(59, &scale), Some(-1));
+
+        let mut tuner = Tuner::default();
+        tuner.set_scale(Arc::new(scale));
+        tuner.set_mapping(Arc::new(mapping));
+
+        let c4 = 440.0 * 3.0 / 5.0;
+        assert_tuning(
+            &tuner,
+            &[
+                (60, Some(c4)),
+                (61, None),
+                (62, Some(c4 * 9.0 / 8.0)),
+                (63, None),
+                (64, Some(c4 * 5.0 / 4.0)),
+                (65, Some(c4 * 4.0 / 3.0)),
+                (66, None),
+                (67, Some(c4 * 3.0 / 2.0)),
+                (69, Some(440.0)),
+                (70, None),
+                (71, Some(c4 * 15.0 / 8.0)),
+                (72, Some(c4 * 2.0)),
+                (59, Some(c4 * 15.0 / 16.0)),
+                (57, Some(220.0)),
+                (49, None),
+                (36, Some(c4 / 4.0)),
+            ],
+        );
+    }
+
+    #[test]
+    fn notes_outside_the_mapped_range_are_unmapped() {
+        let mut mapping = KeyboardMapping::parse(A440).unwrap();
+        mapping.first_note = 21;
+        mapping.last_note = 108;
+        let mut tuner = Tuner::default();
+        tuner.set_mapping(Arc::new(mapping));
+
+        assert_tuning(
+            &tuner,
+            &[
+                (20, None),
+                (21, Some(27.5)),
+                (69, Some(440.0)),
+                (108, Some(4186.009)),
+                (109, None),
+            ],
+        );
+    }
+
+    #[test]
+    fn short_maps_leave_the_trailing_keys_unmapped() {
+        let mapping = KeyboardMapping::parse("3\n0\n127\n60\n69\n440\n12\n0\n4\n").unwrap();
+        assert_eq!(mapping.keys, [Some(0), Some(4), None]);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        assert!(matches!(
+            Scale::parse("too few\n3\n9/8\n5/4\n"),
+            Err(ScalaError::NoteCount {
+                expected: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            Scale::parse("bad ratio\n1\n3/0\n"),
+            Err(ScalaError::InvalidPitch(_))
+        ));
+        assert!(matches!(
+            KeyboardMapping::parse("0\n0\n127\n60\n69\n0\n0\n"),
+            Err(ScalaError::InvalidNumber("reference frequency", _))
+        ));
     }
 }
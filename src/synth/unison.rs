@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::Rng;
+
+pub const MAX_UNISON: usize = 8;
+pub const MAX_DETUNE_CENTS: f32 = 100.0;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum UnisonPhase {
+    // every copy starts at phase 0, which gives a strong transient that then smears out
+    #[default]
+    Reset,
+    Random,
+}
+
+impl UnisonPhase {
+    pub const ALL: [UnisonPhase; 2] = [UnisonPhase::Reset, UnisonPhase::Random];
+}
+
+impl std::fmt::Display for UnisonPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            UnisonPhase::Reset => "Reset",
+            UnisonPhase::Random => "Random",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+// detuned copies of the oscillator stacked on every voice
+// the copies are spread evenly over [-detune, detune] cents and [-spread, spread] pan
+#[derive(Copy, Clone, Debug)]
+pub struct Unison {
+    voices: usize,
+    detune_cents: f32,
+    spread: f32,
+    phase: UnisonPhase,
+    rng: Rng,
+}
+
+impl Default for Unison {
+    fn default() -> Self {
+        Self {
+            voices: 1,
+            detune_cents: 0.0,
+            spread: 0.0,
+            phase: UnisonPhase::default(),
+            rng: Rng::new(0x9E37_79B9),
+        }
+    }
+}
+
+impl Unison {
+    pub fn voices(&self) -> usize {
+        self.voices
+    }
+
+    pub fn set_voices(&mut self, voices: usize) {
+        self.voices = voices.clamp(1, MAX_UNISON);
+    }
+
+    pub fn set_detune(&mut self, cents: f32) {
+        self.detune_cents = cents.clamp(0.0, MAX_DETUNE_CENTS);
+    }
+
+    pub fn set_spread(&mut self, spread: f32) {
+        self.spread = spread.clamp(0.0, 1.0);
+    }
+
+    pub fn set_phase(&mut self, phase: UnisonPhase) {
+        self.phase = phase;
+    }
+
+    // where copy `index` sits between -1 and 1
+    fn offset(&self, index: usize) -> f32 {
+        if self.voices == 1 {
+            0.0
+        } else {
+            2.0 * index as f32 / (self.voices - 1) as f32 - 1.0
+        }
+    }
+
+    pub fn frequency_ratio(&self, index: usize) -> f32 {
+        2.0_f32.powf(self.offset(index) * self.detune_cents / 1200.0)
+    }
+
+    pub fn pan(&self, index: usize) -> f32 {
+        self.offset(index) * self.spread
+    }
+
+    // keeps the loudness roughly constant as copies are added, assuming they are uncorrelated
+    pub fn gain(&self) -> f32 {
+        (self.voices as f32).recip().sqrt()
+    }
+
+    pub fn start_phases(&mut self) -> [f32; MAX_UNISON] {
+        match self.phase {
+            UnisonPhase::Reset => [0.0; MAX_UNISON],
+            UnisonPhase::Random => {
+                std::array::from_fn(|_| std::f32::consts::TAU * self.rng.next_f32())
+            }
+        }
+    }
+}
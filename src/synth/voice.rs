@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::midi::MidiNote;
+use crate::synth::unison::MAX_UNISON;
+use crate::synth::Envelope;
+
+pub const MAX_VOICES: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum VoiceState {
+    Idle,
+    Attacking(MidiNote),
+    Decaying(MidiNote),
+    Sustaining(MidiNote),
+    Releasing(MidiNote),
+}
+
+impl VoiceState {
+    pub fn get_note(&self) -> Option<MidiNote> {
+        match self {
+            VoiceState::Idle => None,
+            VoiceState::Attacking(note) => Some(*note),
+            VoiceState::Decaying(note) => Some(*note),
+            VoiceState::Sustaining(note) => Some(*note),
+            VoiceState::Releasing(note) => Some(*note),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Voice {
+    pub state: VoiceState,
+    pub volume: f32,
+    // gain from the note's velocity
+    pub velocity: f32,
+    // one per unison copy
+    pub phases: [f32; MAX_UNISON],
+    // value of the allocator's clock when the voice was last triggered
+    started_at: u64,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Self {
+            state: VoiceState::Idle,
+            volume: 0.0,
+            velocity: 1.0,
+            phases: [0.0; MAX_UNISON],
+            started_at: 0,
+        }
+    }
+}
+
+impl Voice {
+    fn set_state(&mut self, state: VoiceState) {
+        self.state = state;
+    }
+
+    // advances the envelope by `samples` samples at once
+    pub fn update_envelope(&mut self, envelope: &Envelope, sample_rate: f32, samples: usize) {
+        if let VoiceState::Attacking(note) = self.state {
+            if self.volume >= 1.0 {
+                self.volume = 1.0;
+                self.set_state(VoiceState::Decaying(note));
+            } else {
+                self.volume += samples as f32 * envelope.attack_increment(sample_rate);
+                self.volume = f32::min(self.volume, 1.0);
+            }
+        } else if let VoiceState::Decaying(note) = self.state {
+            if self.volume <= envelope.sustain {
+                self.volume = envelope.sustain;
+                self.set_state(VoiceState::Sustaining(note));
+            } else {
+                self.volume -= samples as f32 * envelope.decay_increment(sample_rate);
+                self.volume = f32::max(self.volume, envelope.sustain);
+            }
+        } else if let VoiceState::Releasing(_) = self.state {
+            if self.volume <= 0.0 {
+                self.volume = 0.0;
+                self.set_state(VoiceState::Idle);
+            } else {
+                self.volume -= samples as f32 * envelope.release_decrement(sample_rate);
+                self.volume = f32::max(self.volume, 0.0);
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum VoiceMode {
+    // a single voice retriggered by every note, like the Model D
+    #[default]
+    Mono,
+    Poly,
+}
+
+impl VoiceMode {
+    pub const ALL: [VoiceMode; 2] = [VoiceMode::Mono, VoiceMode::Poly];
+}
+
+impl std::fmt::Display for VoiceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            VoiceMode::Mono => "Mono",
+            VoiceMode::Poly => "Poly",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+// which voice a new note takes when none is idle
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum VoiceStealing {
+    #[default]
+    Oldest,
+    Quietest,
+    // a note that is already sounding retriggers its own voice, otherwise the oldest is stolen
+    SameNote,
+}
+
+impl VoiceStealing {
+    pub const ALL: [VoiceStealing; 3] = [
+        VoiceStealing::Oldest,
+        VoiceStealing::Quietest,
+        VoiceStealing::SameNote,
+    ];
+}
+
+impl std::fmt::Display for VoiceStealing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            VoiceStealing::Oldest => "Oldest",
+            VoiceStealing::Quietest => "Quietest",
+            VoiceStealing::SameNote => "Same note",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+pub struct VoiceAllocator {
+    voices: [Voice; MAX_VOICES],
+    mode: VoiceMode,
+    polyphony: usize,
+    stealing: VoiceStealing,
+    clock: u64,
+}
+
+impl Default for VoiceAllocator {
+    fn default() -> Self {
+        Self {
+            voices: [Voice::default(); MAX_VOICES],
+            mode: VoiceMode::default(),
+            polyphony: 8,
+            stealing: VoiceStealing::default(),
+            clock: 0,
+        }
+    }
+}
+
+impl VoiceAllocator {
+    fn voice_count(&self) -> usize {
+        match self.mode {
+            VoiceMode::Mono => 1,
+            VoiceMode::Poly => self.polyphony,
+        }
+    }
+
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices[..self.voice_count()]
+    }
+
+    pub fn voices_mut(&mut self) -> &mut [Voice] {
+        let count = self.voice_count();
+        &mut self.voices[..count]
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.voices()
+            .iter()
+            .all(|voice| voice.state == VoiceState::Idle)
+    }
+
+    // a stolen voice keeps its level and phases so that it does not click
+    pub fn note_on(&mut self, note: MidiNote, velocity: u8, start_phases: [f32; MAX_UNISON]) {
+        self.clock += 1;
+        let index = self.allocate(note);
+        let voice = &mut self.voices[index];
+        if voice.state == VoiceState::Idle {
+            voice.phases = start_phases;
+        }
+        voice.started_at = self.clock;
+        // squared, which feels closer to how hard the key was hit than a linear gain
+        voice.velocity = (velocity.min(127) as f32 / 127.0).powi(2);
+        voice.set_state(VoiceState::Attacking(note));
+    }
+
+    pub fn note_off(&mut self, note: MidiNote) {
+        for voice in self.voices_mut() {
+            match voice.state {
+                VoiceState::Idle | VoiceState::Releasing(_) => {}
+                state if state.get_note() == Some(note) => {
+                    voice.set_state(VoiceState::Releasing(note))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: VoiceMode) {
+        self.mode = mode;
+        self.silence_unused();
+    }
+
+    pub fn set_polyphony(&mut self, polyphony: usize) {
+        self.polyphony = polyphony.clamp(1, MAX_VOICES);
+        self.silence_unused();
+    }
+
+    pub fn set_stealing(&mut self, stealing: VoiceStealing) {
+        self.stealing = stealing;
+    }
+
+    fn silence_unused(&mut self) {
+        let count = self.voice_count();
+        for voice in &mut self.voices[count..] {
+            *voice = Voice::default();
+        }
+    }
+
+    fn allocate(&self, note: MidiNote) -> usize {
+        let voices = self.voices();
+        let playing = |voice: &Voice| voice.state.get_note() == Some(note);
+        let idle = |voice: &Voice| voice.state == VoiceState::Idle;
+
+        if self.stealing == VoiceStealing::SameNote {
+            if let Some(index) = voices.iter().position(playing) {
+                return index;
+            }
+        }
+        if let Some(index) = voices.iter().position(idle) {
+            return index;
+        }
+
+        let candidates = voices.iter().enumerate();
+        let stolen = match self.stealing {
+            VoiceStealing::Quietest => candidates
+                .min_by(|(_, a), (_, b)| a.volume.total_cmp(&b.volume))
+                .map(|(index, _)| index),
+            VoiceStealing::Oldest | VoiceStealing::SameNote => candidates
+                .min_by_key(|(_, voice)| voice.started_at)
+                .map(|(index, _)| index),
+        };
+
+        stolen.unwrap_or(0)
+    }
+}
@@ -1,12 +1,29 @@
 use std::f32::consts::TAU;
-use std::sync::Arc;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 use hound::{SampleFormat, WavReader, WavSpec};
+use serde::{Deserialize, Serialize};
 
-#[allow(dead_code)]
-const WAVETABLE_RESOLUTION: usize = 256;
+use crate::math::{self, Complex};
+
+const WAVETABLE_RESOLUTION: usize = 2048;
+// harmonic ladder from 1024 down to 1
+const MIP_LEVELS: usize = 11;
+// windowed sinc kernel, precomputed for `SINC_PHASES` fractional positions
+const SINC_TAPS: usize = 8;
+const SINC_PHASES: usize = 512;
 const FLAT_GAIN_REDUCTION: f32 = 0.7;
+// longer frames are most likely not a single cycle
+const MAX_CYCLE_LENGTH: usize = 1 << 14;
+const MAX_FRAMES: usize = 256;
+// frame size assumed for multi-frame files without a `clm ` chunk
+const SERUM_FRAME_SIZE: usize = 2048;
 
+// built on the GUI side and swapped into the audio thread as a whole, so that the audio
+// thread never allocates for a new table
+#[derive(Clone, Debug)]
 pub struct WavetableBank {
     triangle: Arc<Wavetable>,
     triangle_saw: Arc<Wavetable>,
@@ -14,29 +31,61 @@ pub struct WavetableBank {
     square: Arc<Wavetable>,
     pwm_wide: Arc<Wavetable>,
     pwm_narrow: Arc<Wavetable>,
+    user: Vec<Arc<Wavetable>>,
+}
+
+// the tables embedded in the binary
+impl Default for WavetableBank {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WavetableBank {
+    // the tables embedded in the binary
     pub fn new() -> Self {
-        let triangle: Arc<Wavetable> =
-            Arc::new(Wavetable::from_disk(WavetableKind::Triangle.path()));
-        let triangle_saw: Arc<Wavetable> =
-            Arc::new(Wavetable::from_disk(WavetableKind::TriangleSaw.path()));
-        let saw: Arc<Wavetable> = Arc::new(Wavetable::from_disk(WavetableKind::Saw.path()));
-        let square: Arc<Wavetable> = Arc::new(Wavetable::from_disk(WavetableKind::Square.path()));
-        let pwm_wide: Arc<Wavetable> =
-            Arc::new(Wavetable::from_disk(WavetableKind::PulseWide.path()));
-        let pwm_narrow: Arc<Wavetable> =
-            Arc::new(Wavetable::from_disk(WavetableKind::PulseNarrow.path()));
-
-        Self {
+        Self::load(None).expect("embedded wavetables are valid")
+    }
+
+    // files found in `override_dir` replace the embedded tables of the same name
+    pub fn load(override_dir: Option<&Path>) -> Result<Self, WavetableError> {
+        let load = |kind: WavetableKind| -> Result<Arc<Wavetable>, WavetableError> {
+            let file_name = kind
+                .file_name()
+                .expect("built-in wavetables have a file name");
+            let embedded = kind.embedded().expect("built-in wavetables are embedded");
+            let path: Option<PathBuf> = override_dir
+                .map(|dir| dir.join(file_name))
+                .filter(|path| path.is_file());
+            let wavetable = match path {
+                Some(path) => Wavetable::from_disk(path)?,
+                None => Wavetable::from_bytes(embedded)?,
+            };
+            Ok(Arc::new(wavetable))
+        };
+        let triangle: Arc<Wavetable> = load(WavetableKind::Triangle)?;
+        let triangle_saw: Arc<Wavetable> = load(WavetableKind::TriangleSaw)?;
+        let saw: Arc<Wavetable> = load(WavetableKind::Saw)?;
+        let square: Arc<Wavetable> = load(WavetableKind::Square)?;
+        let pwm_wide: Arc<Wavetable> = load(WavetableKind::PulseWide)?;
+        let pwm_narrow: Arc<Wavetable> = load(WavetableKind::PulseNarrow)?;
+
+        Ok(Self {
             triangle,
             triangle_saw,
             saw,
             square,
             pwm_wide,
             pwm_narrow,
-        }
+            user: Vec::new(),
+        })
+    }
+
+    // user tables are numbered in import order
+    pub fn add(&mut self, wavetable: Arc<Wavetable>) -> WavetableKind {
+        self.user.push(wavetable);
+
+        WavetableKind::User(self.user.len() - 1)
     }
 
     pub fn get(&self, kind: WavetableKind) -> Arc<Wavetable> {
@@ -47,71 +96,346 @@ impl WavetableBank {
             WavetableKind::Square => self.square.clone(),
             WavetableKind::PulseWide => self.pwm_wide.clone(),
             WavetableKind::PulseNarrow => self.pwm_narrow.clone(),
+            WavetableKind::User(index) => self.user.get(index).unwrap_or(&self.triangle).clone(),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+// levels[level][index]
+type MipLevels = Box<[Box<[f32]>]>;
+
+// one or more frames, each holding a band-limited copy of its cycle per octave
+// level `k` only keeps harmonics up to `(WAVETABLE_RESOLUTION / 2) >> k`
+#[derive(Clone)]
 pub struct Wavetable {
-    data: Arc<[f32]>,
+    frames: Arc<[MipLevels]>,
     size: usize,
 }
 
+// the samples would be hundreds of thousands of numbers
+impl std::fmt::Debug for Wavetable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wavetable")
+            .field("frames", &self.frames.len())
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
 impl Wavetable {
-    pub fn from_disk(path: &str) -> Self {
-        let reader = WavReader::open(path).unwrap();
-        let size: usize = reader.len() as usize;
-        let data: Vec<f32> = match reader.spec() {
+    pub fn from_disk(path: impl AsRef<Path>) -> Result<Self, WavetableError> {
+        let path = path.as_ref();
+        let bytes: Vec<u8> = std::fs::read(path).map_err(|err| {
+            WavetableError::InFile(path.to_path_buf(), Box::new(hound::Error::from(err).into()))
+        })?;
+
+        Self::from_bytes(&bytes)
+            .map_err(|err| WavetableError::InFile(path.to_path_buf(), Box::new(err)))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WavetableError> {
+        let frame_size: Option<usize> = clm_frame_size(bytes);
+
+        Self::from_wav(WavReader::new(Cursor::new(bytes))?, frame_size)
+    }
+
+    // any bit depth, channels are mixed down to mono
+    // without a frame size, files made of several `SERUM_FRAME_SIZE` frames are split,
+    // anything else is taken as a single cycle
+    fn from_wav<R: Read>(
+        reader: WavReader<R>,
+        frame_size: Option<usize>,
+    ) -> Result<Self, WavetableError> {
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec {
             WavSpec {
                 sample_format: SampleFormat::Int,
+                bits_per_sample,
                 ..
-            } => reader
-                .into_samples::<i32>()
-                .map(|x| x.unwrap())
-                .map(|x| if x == i32::MIN { i32::MIN + 1 } else { x })
-                .map(|x| x as f32 / i32::MAX as f32)
-                .collect(),
+            } => {
+                let full_scale = (1_i64 << (bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|x| x.map(|x| x as f32 / full_scale))
+                    .collect::<Result<_, _>>()?
+            }
             WavSpec {
                 sample_format: SampleFormat::Float,
                 ..
-            } => reader.into_samples::<f32>().map(|x| x.unwrap()).collect(),
+            } => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
         };
-        let infinite_norm: f32 = data.iter().map(|x: &f32| x.abs()).fold(0.0, f32::max);
 
-        let data: Vec<f32> = if infinite_norm > 0.0 {
-            data.iter()
-                .map(|x| FLAT_GAIN_REDUCTION * *x / infinite_norm)
-                .collect()
-        } else {
-            data
+        let channels = usize::max(spec.channels as usize, 1);
+        let data: Vec<f32> = interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+
+        if data.is_empty() {
+            return Err(WavetableError::Empty);
+        }
+        if data.iter().any(|x| !x.is_finite()) {
+            return Err(WavetableError::NonFinite);
+        }
+
+        let frame_size: usize = match frame_size {
+            Some(frame_size) => frame_size.min(data.len()),
+            None if data.len() > SERUM_FRAME_SIZE
+                && data.len().is_multiple_of(SERUM_FRAME_SIZE) =>
+            {
+                SERUM_FRAME_SIZE
+            }
+            None => data.len(),
         };
-        let data: Arc<[f32]> = Arc::from(data);
+        if frame_size > MAX_CYCLE_LENGTH {
+            return Err(WavetableError::TooLong(frame_size));
+        }
+        // a frame size of 0 from a broken header ends up here too
+        if !data.len().is_multiple_of(frame_size) {
+            return Err(WavetableError::PartialFrame(data.len(), frame_size));
+        }
+        let cycles: Vec<&[f32]> = data.chunks_exact(frame_size).collect();
+        if cycles.len() > MAX_FRAMES {
+            return Err(WavetableError::TooManyFrames(cycles.len()));
+        }
 
-        Self { data, size }
+        Ok(Self::from_cycles(&cycles))
     }
 
     #[allow(dead_code)]
     pub fn pure_sine() -> Self {
+        Self::harmonic_sine(1)
+    }
+
+    // `harmonic` periods of a sine per cycle, read `harmonic` times faster than the table,
+    // so that interpolation has fewer samples per period to work with
+    #[allow(dead_code)]
+    pub fn harmonic_sine(harmonic: usize) -> Self {
         let size = WAVETABLE_RESOLUTION;
-        let data: Arc<[f32]> = (0..size)
-            .map(|i| TAU * (i as f32) / (size as f32))
+        let data: Vec<f32> = (0..size)
+            .map(|i| TAU * ((harmonic * i) % size) as f32 / (size as f32))
             .map(|phase| phase.sin())
             .collect();
 
-        Self { data, size }
+        Self::from_cycle(&data)
+    }
+
+    pub fn from_cycle(cycle: &[f32]) -> Self {
+        Self::from_cycles(&[cycle])
+    }
+
+    // builds the mip levels of every frame from cycles of any length
+    // each cycle is resampled to `WAVETABLE_RESOLUTION` in the frequency domain
+    pub fn from_cycles(cycles: &[&[f32]]) -> Self {
+        let size = WAVETABLE_RESOLUTION;
+        let n_levels = MIP_LEVELS;
+
+        let frames: Vec<Vec<Vec<f32>>> = cycles
+            .iter()
+            .map(|cycle| {
+                let spectrum: Vec<Complex> = math::dft(cycle)
+                    .iter()
+                    .map(|x| x.scale(1.0 / cycle.len() as f32))
+                    .collect();
+                let max_source_harmonic: usize = (cycle.len().max(1) - 1) / 2;
+
+                (0..n_levels)
+                    .map(|level| {
+                        let max_harmonic = max_harmonic_at_level(level).min(max_source_harmonic);
+                        band_limited_cycle(&spectrum, max_harmonic, size)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // every level of every frame shares the gain of the loudest brightest level
+        // so that crossfading is seamless in both directions
+        let infinite_norm: f32 = frames
+            .iter()
+            .flat_map(|levels| levels[0].iter())
+            .map(|x: &f32| x.abs())
+            .fold(0.0, f32::max);
+        let gain: f32 = if infinite_norm > 0.0 {
+            FLAT_GAIN_REDUCTION / infinite_norm
+        } else {
+            1.0
+        };
+        let frames: Arc<[MipLevels]> = frames
+            .into_iter()
+            .map(|levels| {
+                levels
+                    .into_iter()
+                    .map(|level| level.iter().map(|x| gain * x).collect())
+                    .collect()
+            })
+            .collect();
+
+        Self { frames, size }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
     }
 
     // 2π periodic
-    pub fn at(&self, phase: f32) -> f32 {
+    // `position` in [0, 1] sweeps through the frames
+    // reads from the mip levels that are free of aliasing at `frequency`
+    pub fn at(
+        &self,
+        phase: f32,
+        position: f32,
+        frequency: f32,
+        sample_rate: f32,
+        interpolation: Interpolation,
+    ) -> f32 {
+        let float_frame = position.clamp(0.0, 1.0) * (self.frames.len() - 1) as f32;
+        let frame: usize = float_frame.floor() as usize;
+        let next_frame: usize = usize::min(frame + 1, self.frames.len() - 1);
+
+        let (level, crossfade) = self.level_for(frequency, sample_rate);
         let float_index = self.size as f32 * phase.rem_euclid(TAU) / TAU;
-        let lower: usize = float_index.floor() as usize;
-        let higher: usize = wrapped_increment(lower, self.size - 1);
+        let a = self.read_frame(frame, level, crossfade, float_index, interpolation);
+        if next_frame == frame || float_frame.fract() == 0.0 {
+            return a;
+        }
+        let b = self.read_frame(next_frame, level, crossfade, float_index, interpolation);
+
+        math::lerp(float_frame.fract(), a, b)
+    }
+
+    fn read_frame(
+        &self,
+        frame: usize,
+        level: usize,
+        crossfade: f32,
+        float_index: f32,
+        interpolation: Interpolation,
+    ) -> f32 {
+        let levels = &self.frames[frame];
+        let next_level = usize::min(level + 1, levels.len() - 1);
+
+        let a = self.read(&levels[level], float_index, interpolation);
+        if crossfade == 0.0 || next_level == level {
+            return a;
+        }
+        let b = self.read(&levels[next_level], float_index, interpolation);
+
+        math::lerp(crossfade, a, b)
+    }
+
+    fn read(&self, data: &[f32], float_index: f32, interpolation: Interpolation) -> f32 {
+        let index: usize = float_index.floor() as usize;
+        let x: f32 = float_index.fract();
+        let tap = |offset: isize| -> f32 {
+            data[(index as isize + offset).rem_euclid(self.size as isize) as usize]
+        };
+
+        match interpolation {
+            Interpolation::Linear => math::lerp(x, tap(0), tap(1)),
+            Interpolation::CubicHermite => math::hermite(x, tap(-1), tap(0), tap(1), tap(2)),
+            Interpolation::Lagrange => math::lagrange(x, tap(-1), tap(0), tap(1), tap(2)),
+            Interpolation::WindowedSinc => {
+                let phase: usize = (x * SINC_PHASES as f32).round() as usize;
+                let (offset, phase) = if phase == SINC_PHASES {
+                    (1, 0)
+                } else {
+                    (0, phase)
+                };
+                let first_tap = offset + 1 - (SINC_TAPS / 2) as isize;
+                sinc_kernel()[phase]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, coefficient)| coefficient * tap(first_tap + i as isize))
+                    .sum()
+            }
+        }
+    }
+
+    // the first level and the crossfade towards the next one
+    // level `floor(x) + 1` never has harmonics above Nyquist, hence the offset
+    fn level_for(&self, frequency: f32, sample_rate: f32) -> (usize, f32) {
+        let allowed_harmonics = 0.5 * sample_rate / frequency.abs().max(f32::EPSILON);
+        let x = f32::log2(max_harmonic_at_level(0) as f32 / allowed_harmonics);
+        if x < -1.0 {
+            return (0, 0.0);
+        }
+
+        let level = (x.floor() + 1.0) as usize;
+        if level >= MIP_LEVELS - 1 {
+            return (MIP_LEVELS - 1, 0.0);
+        }
 
-        crate::math::lerp(float_index.fract(), self.data[lower], self.data[higher])
+        (level, x - x.floor())
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+// `kernel[p][i]` weighs tap `i - SINC_TAPS / 2 + 1` at fractional position `p / SINC_PHASES`
+fn sinc_kernel() -> &'static [[f32; SINC_TAPS]] {
+    static KERNEL: OnceLock<Vec<[f32; SINC_TAPS]>> = OnceLock::new();
+    KERNEL.get_or_init(|| {
+        let half_width = (SINC_TAPS / 2) as f32;
+        (0..SINC_PHASES)
+            .map(|phase| {
+                let x = phase as f32 / SINC_PHASES as f32;
+                let mut taps = [0.0; SINC_TAPS];
+                for (i, coefficient) in taps.iter_mut().enumerate() {
+                    let distance = (i as f32 - half_width + 1.0) - x;
+                    *coefficient = math::sinc(distance) * math::blackman(distance / half_width);
+                }
+                let sum: f32 = taps.iter().sum();
+                taps.map(|coefficient| coefficient / sum)
+            })
+            .collect()
+    })
+}
+
+// Serum writes the frame size in a `clm ` chunk, e.g. `<!>2048 10000000 wavetable (...)`
+fn clm_frame_size(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut offset: usize = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let len = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = bytes.get(offset + 8..offset + 8 + len)?;
+        if id == b"clm " {
+            let text = std::str::from_utf8(body).ok()?;
+            let digits: String = text
+                .strip_prefix("<!>")?
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            return digits.parse().ok().filter(|size| *size > 0);
+        }
+        // chunks are word aligned
+        offset += 8 + len + (len & 1);
+    }
+
+    None
+}
+
+fn max_harmonic_at_level(level: usize) -> usize {
+    (WAVETABLE_RESOLUTION / 2) >> level
+}
+
+// inverse transform keeping harmonics `1..=max_harmonic`
+// the table's own Nyquist bin is always dropped
+fn band_limited_cycle(spectrum: &[Complex], max_harmonic: usize, size: usize) -> Vec<f32> {
+    let max_harmonic = max_harmonic.min(size / 2 - 1);
+    let mut bins: Vec<Complex> = vec![Complex::default(); size];
+    bins[0] = spectrum.first().copied().unwrap_or_default();
+    for k in 1..=max_harmonic {
+        bins[k] = spectrum[k];
+        bins[size - k] = spectrum[k].conj();
+    }
+    math::ifft(&mut bins);
+
+    bins.iter().map(|x| x.re * size as f32).collect()
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum WavetableKind {
     Triangle,
     TriangleSaw,
@@ -119,17 +443,37 @@ pub enum WavetableKind {
     Square,
     PulseWide,
     PulseNarrow,
+    // imported at runtime, see `WavetableBank::add`
+    User(usize),
 }
 
 impl WavetableKind {
-    pub fn path(&self) -> &'static str {
+    pub fn file_name(&self) -> Option<&'static str> {
         match self {
-            WavetableKind::Triangle => "./assets/wavetables/mini_triangle_wavetable.wav",
-            WavetableKind::TriangleSaw => "./assets/wavetables/mini_triangle_saw_wavetable.wav",
-            WavetableKind::Saw => "./assets/wavetables/mini_saw_wavetable.wav",
-            WavetableKind::Square => "./assets/wavetables/mini_square_wavetable.wav",
-            WavetableKind::PulseWide => "./assets/wavetables/mini_pwm_wide_wavetable.wav",
-            WavetableKind::PulseNarrow => "./assets/wavetables/mini_pwm_narrow_wavetable.wav",
+            WavetableKind::Triangle => Some("mini_triangle_wavetable.wav"),
+            WavetableKind::TriangleSaw => Some("mini_triangle_saw_wavetable.wav"),
+            WavetableKind::Saw => Some("mini_saw_wavetable.wav"),
+            WavetableKind::Square => Some("mini_square_wavetable.wav"),
+            WavetableKind::PulseWide => Some("mini_pwm_wide_wavetable.wav"),
+            WavetableKind::PulseNarrow => Some("mini_pwm_narrow_wavetable.wav"),
+            WavetableKind::User(_) => None,
+        }
+    }
+
+    pub fn embedded(&self) -> Option<&'static [u8]> {
+        macro_rules! embed {
+            ($file:literal) => {
+                include_bytes!(concat!("../../assets/wavetables/", $file))
+            };
+        }
+        match self {
+            WavetableKind::Triangle => Some(embed!("mini_triangle_wavetable.wav")),
+            WavetableKind::TriangleSaw => Some(embed!("mini_triangle_saw_wavetable.wav")),
+            WavetableKind::Saw => Some(embed!("mini_saw_wavetable.wav")),
+            WavetableKind::Square => Some(embed!("mini_square_wavetable.wav")),
+            WavetableKind::PulseWide => Some(embed!("mini_pwm_wide_wavetable.wav")),
+            WavetableKind::PulseNarrow => Some(embed!("mini_pwm_narrow_wavetable.wav")),
+            WavetableKind::User(_) => None,
         }
     }
 }
@@ -143,15 +487,231 @@ impl std::fmt::Display for WavetableKind {
             WavetableKind::Square => "Square",
             WavetableKind::PulseWide => "PWM Wide",
             WavetableKind::PulseNarrow => "PWM Narrow",
+            WavetableKind::User(index) => return write!(f, "User {}", index + 1),
         };
         write!(f, "{}", repr)
     }
 }
 
-fn wrapped_increment(n: usize, max: usize) -> usize {
-    if n == max {
-        0
-    } else {
-        n + 1
+#[derive(Copy, Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    CubicHermite,
+    Lagrange,
+    WindowedSinc,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 4] = [
+        Interpolation::Linear,
+        Interpolation::CubicHermite,
+        Interpolation::Lagrange,
+        Interpolation::WindowedSinc,
+    ];
+}
+
+impl std::fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let repr: &'static str = match self {
+            Interpolation::Linear => "Linear",
+            Interpolation::CubicHermite => "Cubic Hermite",
+            Interpolation::Lagrange => "Lagrange",
+            Interpolation::WindowedSinc => "Windowed Sinc",
+        };
+        write!(f, "{}", repr)
+    }
+}
+
+#[derive(Debug)]
+pub enum WavetableError {
+    Wav(hound::Error),
+    Empty,
+    TooLong(usize),
+    TooManyFrames(usize),
+    // the number of samples and the frame size
+    PartialFrame(usize, usize),
+    NonFinite,
+    InFile(PathBuf, Box<WavetableError>),
+}
+
+impl From<hound::Error> for WavetableError {
+    fn from(err: hound::Error) -> Self {
+        WavetableError::Wav(err)
+    }
+}
+
+impl std::fmt::Display for WavetableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WavetableError::Wav(err) => write!(f, "{}", err),
+            WavetableError::Empty => write!(f, "the file contains no samples"),
+            WavetableError::TooLong(len) => write!(
+                f,
+                "{} samples is too long for a single cycle (max {})",
+                len, MAX_CYCLE_LENGTH
+            ),
+            WavetableError::TooManyFrames(frames) => {
+                write!(f, "{} frames is too many (max {})", frames, MAX_FRAMES)
+            }
+            WavetableError::PartialFrame(len, frame_size) => write!(
+                f,
+                "{} samples is not a whole number of {}-sample frames",
+                len, frame_size
+            ),
+            WavetableError::NonFinite => write!(f, "the file contains NaN or infinite samples"),
+            WavetableError::InFile(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for WavetableError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+    const FFT_SIZE: usize = 1 << 14;
+    // bins around each harmonic that belong to it, the window's main lobe is 4 bins wide
+    // on either side
+    const LOBE: usize = 6;
+
+    // 4-term Blackman-Harris, sidelobes below -92 dB
+    fn window(i: usize) -> f32 {
+        let x = std::f64::consts::TAU * i as f64 / FFT_SIZE as f64;
+        (0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos()) as f32
+    }
+
+    fn power_spectrum(
+        wavetable: &Wavetable,
+        frequency: f32,
+        interpolation: Interpolation,
+    ) -> Vec<f32> {
+        let increment = std::f64::consts::TAU * frequency as f64 / SAMPLE_RATE as f64;
+        let mut bins: Vec<Complex> = (0..FFT_SIZE)
+            .map(|i| {
+                let phase = (increment * i as f64) % std::f64::consts::TAU;
+                let sample = wavetable.at(phase as f32, 0.0, frequency, SAMPLE_RATE, interpolation);
+                Complex::new(window(i) * sample, 0.0)
+            })
+            .collect();
+        math::fft(&mut bins);
+        bins[..FFT_SIZE / 2]
+            .iter()
+            .map(|x| x.norm().powi(2))
+            .collect()
+    }
+
+    // the energy away from the harmonics, relative to all of it
+    fn inharmonic_db(spectrum: &[f32], frequency: f32) -> f32 {
+        let bin_hz = SAMPLE_RATE / FFT_SIZE as f32;
+        let is_harmonic = |bin: usize| {
+            let hz = bin as f32 * bin_hz;
+            let nearest = (hz / frequency).round() * frequency;
+            // DC is left out of both sums
+            bin < LOBE || (nearest > 0.0 && (hz - nearest).abs() <= LOBE as f32 * bin_hz)
+        };
+        let total: f32 = spectrum.iter().skip(LOBE).sum();
+        let inharmonic: f32 = spectrum
+            .iter()
+            .enumerate()
+            .filter(|(bin, _)| !is_harmonic(*bin))
+            .map(|(_, power)| power)
+            .sum();
+
+        10.0 * (inharmonic / total).log10()
+    }
+
+    // a naive saw measures around -10 dB here
+    const MAX_INHARMONIC_DB: f32 = -80.0;
+
+    #[test]
+    fn no_aliasing_near_nyquist() {
+        let bank = WavetableBank::new();
+        for kind in [WavetableKind::Saw, WavetableKind::Square] {
+            // the edges of the mip levels and the top octaves
+            for frequency in [3_000.0, 6_000.0, 9_000.0, 12_000.0, 14_080.0, 19_000.0] {
+                for interpolation in Interpolation::ALL {
+                    let spectrum = power_spectrum(&bank.get(kind), frequency, interpolation);
+                    let db = inharmonic_db(&spectrum, frequency);
+                    assert!(
+                        db < MAX_INHARMONIC_DB,
+                        "{kind} at {frequency} Hz with {interpolation}: {db:.1} dB"
+                    );
+                }
+            }
+        }
+    }
+
+    // `cycles` periods over the fft of the sine in a `Wavetable::harmonic_sine`, read through
+    // the table like a voice would
+    fn render_sine(
+        sine: &Wavetable,
+        harmonic: usize,
+        cycles: usize,
+        interpolation: Interpolation,
+    ) -> Vec<f32> {
+        let frequency = cycles as f32 * SAMPLE_RATE / (harmonic * FFT_SIZE) as f32;
+        let period = harmonic * FFT_SIZE;
+        (0..FFT_SIZE)
+            .map(|i| {
+                let phase = std::f64::consts::TAU * ((cycles * i) % period) as f64 / period as f64;
+                sine.at(phase as f32, 0.0, frequency, SAMPLE_RATE, interpolation)
+            })
+            .collect()
+    }
+
+    // about 1 and 10 kHz, odd so that the phases do not repeat within a table period
+    const THD_CYCLES: [usize; 2] = [341, 3413];
+
+    // with 2048 samples per period every mode is at the f32 floor, between -122 and -124 dB;
+    // reading the nearest sample instead would be around -60 dB
+    #[test]
+    fn interpolation_of_a_well_sampled_sine_is_clean() {
+        let sine = Wavetable::pure_sine();
+        for cycles in THD_CYCLES {
+            for interpolation in Interpolation::ALL {
+                let db = math::thd_n_db(&render_sine(&sine, 1, cycles, interpolation), cycles);
+                assert!(
+                    db < -110.0,
+                    "{interpolation} at {cycles} cycles: THD+N {db:.1} dB"
+                );
+            }
+        }
+    }
+
+    // 8 samples per period, where the modes part: Linear measures -32 dB, Cubic Hermite -44,
+    // Lagrange -51 and Windowed Sinc -64, held back by its `SINC_PHASES` kernel phases
+    const SHORT_PERIOD_HARMONIC: usize = 256;
+
+    fn max_thd_n_db(interpolation: Interpolation) -> f32 {
+        match interpolation {
+            Interpolation::Linear => -30.0,
+            Interpolation::CubicHermite => -42.0,
+            Interpolation::Lagrange => -49.0,
+            Interpolation::WindowedSinc => -62.0,
+        }
+    }
+
+    #[test]
+    fn interpolation_distortion_of_a_short_period() {
+        let sine = Wavetable::harmonic_sine(SHORT_PERIOD_HARMONIC);
+        for cycles in THD_CYCLES {
+            let db = Interpolation::ALL.map(|interpolation| {
+                let signal = render_sine(&sine, SHORT_PERIOD_HARMONIC, cycles, interpolation);
+                let db = math::thd_n_db(&signal, cycles);
+                assert!(
+                    db < max_thd_n_db(interpolation),
+                    "{interpolation} at {cycles} cycles: THD+N {db:.1} dB"
+                );
+                db
+            });
+            let [linear, hermite, lagrange, sinc] = db;
+            assert!(
+                sinc < lagrange.min(hermite) && lagrange.max(hermite) < linear,
+                "out of order at {cycles} cycles: {db:.1?} dB"
+            );
+        }
     }
 }
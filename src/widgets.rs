@@ -0,0 +1,332 @@
+use std::f32::consts::TAU;
+use std::ops::RangeInclusive;
+
+use egui::emath::Numeric;
+use egui::{
+    Align2, FontId, PointerButton, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2, Widget,
+};
+
+pub const KNOB_WIDTH: f32 = 56.0;
+const KNOB_RADIUS: f32 = 16.0;
+const TEXT_HEIGHT: f32 = 14.0;
+const HEIGHT: f32 = 2.0 * TEXT_HEIGHT + 2.0 * KNOB_RADIUS + 6.0;
+// the knob turns through three quarters of a circle, the gap is at the bottom
+const SWEEP: f32 = 0.75 * TAU;
+// of drag, up or right, to turn the knob from one end to the other
+const DRAG_PIXELS: f32 = 200.0;
+// holding shift while dragging or scrolling
+const FINE: f64 = 0.1;
+// of the range, per scroll wheel notch
+const SCROLL_STEP: f64 = 0.02;
+
+// a rotary control, dragged up or right to turn it up
+// shift for finer steps, double-click to reset, the scroll wheel turns it too
+pub struct Knob<'a, Num: Numeric> {
+    label: String,
+    value: &'a mut Num,
+    range: RangeInclusive<Num>,
+    default: Option<Num>,
+    logarithmic: bool,
+    decimals: usize,
+    suffix: String,
+}
+
+impl<'a, Num: Numeric> Knob<'a, Num> {
+    pub fn new(label: impl Into<String>, value: &'a mut Num, range: RangeInclusive<Num>) -> Self {
+        Self {
+            label: label.into(),
+            value,
+            range,
+            default: None,
+            logarithmic: false,
+            decimals: if Num::INTEGRAL { 0 } else { 2 },
+            suffix: String::new(),
+        }
+    }
+
+    // what a double-click goes back to
+    pub fn default_value(mut self, default: Num) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    // the range must be above zero
+    pub fn logarithmic(mut self, logarithmic: bool) -> Self {
+        self.logarithmic = logarithmic;
+        self
+    }
+
+    pub fn decimals(mut self, decimals: usize) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    fn bounds(&self) -> (f64, f64) {
+        (self.range.start().to_f64(), self.range.end().to_f64())
+    }
+
+    // from the value to the knob's position between 0 and 1
+    fn normalize(&self, value: f64) -> f64 {
+        let (min, max) = self.bounds();
+        let value = value.clamp(min, max);
+        if max <= min {
+            0.0
+        } else if self.logarithmic {
+            (value / min).ln() / (max / min).ln()
+        } else {
+            (value - min) / (max - min)
+        }
+    }
+
+    fn denormalize(&self, position: f64) -> f64 {
+        let (min, max) = self.bounds();
+        let position = position.clamp(0.0, 1.0);
+        let value = if self.logarithmic {
+            min * (max / min).powf(position)
+        } else {
+            min + position * (max - min)
+        };
+        if Num::INTEGRAL {
+            value.round()
+        } else {
+            value
+        }
+    }
+}
+
+impl<Num: Numeric> Widget for Knob<'_, Num> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, mut response) =
+            ui.allocate_exact_size(Vec2::new(KNOB_WIDTH, HEIGHT), Sense::click_and_drag());
+        let id = response.id;
+        let old = self.value.to_f64();
+        let mut value = old;
+        let fine = ui.input(|i| i.modifiers.shift);
+
+        if response.drag_started_by(PointerButton::Primary) {
+            ui.data_mut(|data| data.insert_temp(id, self.normalize(old)));
+        }
+        if response.dragged_by(PointerButton::Primary) {
+            let delta = response.drag_delta();
+            let mut amount = ((delta.x - delta.y) / DRAG_PIXELS) as f64;
+            if fine {
+                amount *= FINE;
+            }
+            // kept unrounded, so that slow drags still move integer knobs
+            let start = ui
+                .data(|data| data.get_temp(id))
+                .unwrap_or(self.normalize(old));
+            let position = (start + amount).clamp(0.0, 1.0);
+            ui.data_mut(|data| data.insert_temp(id, position));
+            value = self.denormalize(position);
+        }
+        if response.hovered() && ui.is_enabled() {
+            let scroll = ui.input(|i| i.raw_scroll_delta);
+            let notch = (scroll.x + scroll.y).signum() as f64;
+            if scroll != Vec2::ZERO {
+                let step = if fine {
+                    FINE * SCROLL_STEP
+                } else {
+                    SCROLL_STEP
+                };
+                value = self.denormalize(self.normalize(old) + notch * step);
+                // integer knobs move by at least one per notch, steps smaller than that
+                // would round back to where they were
+                if Num::INTEGRAL && value == old {
+                    let (min, max) = self.bounds();
+                    value = (old + notch).clamp(min, max);
+                }
+            }
+            // the wheel turns the knob rather than scrolling the panel
+            ui.ctx().input_mut(|i| i.smooth_scroll_delta = Vec2::ZERO);
+        }
+        if response.double_clicked() {
+            if let Some(default) = self.default {
+                value = default.to_f64();
+            }
+        }
+        if value != old {
+            *self.value = Num::from_f64(value);
+            response.mark_changed();
+        }
+
+        let value = self.value.to_f64();
+        response.widget_info(|| egui::WidgetInfo::slider(ui.is_enabled(), value, &self.label));
+        if ui.is_rect_visible(rect) {
+            let (min, max) = self.bounds();
+            // knobs that go both ways light up from zero
+            let origin = if min < 0.0 && max > 0.0 && !self.logarithmic {
+                self.normalize(0.0)
+            } else {
+                0.0
+            };
+            paint_knob(
+                ui,
+                &response,
+                rect,
+                &self.label,
+                &format!("{:.*}{}", self.decimals, value, self.suffix),
+                origin as f32,
+                self.normalize(value) as f32,
+            );
+        }
+
+        response
+    }
+}
+
+// an on/off lever, flipped with a click
+pub struct Toggle<'a> {
+    label: String,
+    value: &'a mut bool,
+}
+
+impl<'a> Toggle<'a> {
+    pub fn new(label: impl Into<String>, value: &'a mut bool) -> Self {
+        Self {
+            label: label.into(),
+            value,
+        }
+    }
+}
+
+impl Widget for Toggle<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let (rect, mut response) =
+            ui.allocate_exact_size(Vec2::new(KNOB_WIDTH, HEIGHT), Sense::click());
+        if response.clicked() {
+            *self.value = !*self.value;
+            response.mark_changed();
+        }
+        let on = *self.value;
+        response.widget_info(|| {
+            egui::WidgetInfo::selected(egui::WidgetType::Checkbox, ui.is_enabled(), on, &self.label)
+        });
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let painter = ui.painter();
+            let text_color = ui.visuals().text_color();
+            painter.text(
+                Pos2::new(rect.center().x, rect.top()),
+                Align2::CENTER_TOP,
+                &self.label,
+                FontId::proportional(11.0),
+                text_color,
+            );
+            let slot = Rect::from_center_size(
+                Pos2::new(
+                    rect.center().x,
+                    rect.top() + TEXT_HEIGHT + KNOB_RADIUS + 3.0,
+                ),
+                Vec2::new(0.8 * KNOB_RADIUS, 1.8 * KNOB_RADIUS),
+            );
+            let lit = ui.visuals().selection.bg_fill;
+            painter.rect(
+                slot,
+                0.4 * KNOB_RADIUS,
+                if on { lit } else { visuals.bg_fill },
+                visuals.bg_stroke,
+                egui::StrokeKind::Inside,
+            );
+            let lever = if on {
+                slot.center_top() + Vec2::new(0.0, 0.4 * KNOB_RADIUS)
+            } else {
+                slot.center_bottom() - Vec2::new(0.0, 0.4 * KNOB_RADIUS)
+            };
+            painter.circle(
+                lever,
+                0.3 * KNOB_RADIUS,
+                visuals.fg_stroke.color,
+                Stroke::NONE,
+            );
+            painter.text(
+                Pos2::new(rect.center().x, rect.bottom()),
+                Align2::CENTER_BOTTOM,
+                if on { "On" } else { "Off" },
+                FontId::proportional(11.0),
+                text_color,
+            );
+        }
+
+        response
+    }
+}
+
+// angles are clockwise from straight up, `position` goes from 0 to 1 across the sweep
+fn knob_direction(position: f32) -> Vec2 {
+    let angle = (position - 0.5) * SWEEP;
+    Vec2::new(angle.sin(), -angle.cos())
+}
+
+fn arc(center: Pos2, radius: f32, from: f32, to: f32) -> Vec<Pos2> {
+    let (from, to) = (from.min(to), from.max(to));
+    let steps = ((to - from) * 48.0).ceil().max(1.0) as usize;
+    (0..=steps)
+        .map(|step| {
+            let position = from + (to - from) * step as f32 / steps as f32;
+            center + radius * knob_direction(position)
+        })
+        .collect()
+}
+
+fn paint_knob(
+    ui: &Ui,
+    response: &Response,
+    rect: Rect,
+    label: &str,
+    value: &str,
+    origin: f32,
+    position: f32,
+) {
+    let visuals = ui.style().interact(response);
+    let painter = ui.painter();
+    let text_color = ui.visuals().text_color();
+    let center = Pos2::new(
+        rect.center().x,
+        rect.top() + TEXT_HEIGHT + KNOB_RADIUS + 3.0,
+    );
+
+    painter.text(
+        Pos2::new(rect.center().x, rect.top()),
+        Align2::CENTER_TOP,
+        label,
+        FontId::proportional(11.0),
+        text_color,
+    );
+    painter.add(Shape::line(
+        arc(center, KNOB_RADIUS, 0.0, 1.0),
+        Stroke::new(3.0, ui.visuals().extreme_bg_color),
+    ));
+    if position != origin {
+        painter.add(Shape::line(
+            arc(center, KNOB_RADIUS, origin, position),
+            Stroke::new(3.0, ui.visuals().selection.bg_fill),
+        ));
+    }
+    painter.circle(
+        center,
+        0.72 * KNOB_RADIUS,
+        visuals.bg_fill,
+        visuals.bg_stroke,
+    );
+    painter.line_segment(
+        [
+            center + 0.25 * KNOB_RADIUS * knob_direction(position),
+            center + 0.72 * KNOB_RADIUS * knob_direction(position),
+        ],
+        Stroke::new(2.0, visuals.fg_stroke.color),
+    );
+    painter.text(
+        Pos2::new(rect.center().x, rect.bottom()),
+        Align2::CENTER_BOTTOM,
+        value,
+        FontId::proportional(11.0),
+        text_color,
+    );
+}